@@ -0,0 +1,198 @@
+//! Verifies downloaded files against the checksums advertised by the API.
+
+use anyhow::Result;
+use md5::Md5;
+use sha1::Sha1;
+use sha2::{Digest, Sha256, Sha512};
+
+use std::fmt::Display;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::download;
+use crate::extractor::Course;
+use crate::types::episodes::Checksum;
+
+/// The result of checking a file against the checksum advertised by the API
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Verification {
+    /// The checksum matches
+    Match,
+    /// The checksum doesn't match
+    Mismatch {
+        /// The checksum advertised by the API (in lower case)
+        expected: String,
+        /// The checksum of the file
+        actual: String,
+    },
+    /// The API didn't advertise a checksum
+    Missing,
+    /// The API advertised a checksum with an algorithm we don't know
+    Unsupported(String),
+}
+
+impl Verification {
+    /// Whether the file is known to be broken
+    pub fn is_mismatch(&self) -> bool {
+        matches!(self, Verification::Mismatch { .. })
+    }
+}
+
+/// Hashes a file with the algorithm of the checksum and compares the result
+pub fn verify_file(path: &Path, checksum: Option<&Checksum>) -> Result<Verification> {
+    let checksum = match checksum {
+        Some(checksum) => checksum,
+        None => return Ok(Verification::Missing),
+    };
+
+    let actual = match hash_file(path, &checksum.type_field)? {
+        Some(actual) => actual,
+        None => return Ok(Verification::Unsupported(checksum.type_field.to_owned())),
+    };
+
+    Ok(if actual.eq_ignore_ascii_case(checksum.field.trim()) {
+        Verification::Match
+    } else {
+        Verification::Mismatch {
+            expected: checksum.field.trim().to_lowercase(),
+            actual,
+        }
+    })
+}
+
+/// Hashes a file, returning the lower-case hex digest (or `None` if the algorithm is unknown)
+fn hash_file(path: &Path, algorithm: &str) -> Result<Option<String>> {
+    // Opencast reports e.g. `md5`, `sha1` or `SHA-256`
+    let algorithm = algorithm.to_lowercase().replace('-', "");
+
+    Ok(match algorithm.as_str() {
+        "md5" => Some(hash_with::<Md5>(path)?),
+        "sha1" => Some(hash_with::<Sha1>(path)?),
+        "sha256" => Some(hash_with::<Sha256>(path)?),
+        "sha512" => Some(hash_with::<Sha512>(path)?),
+        _ => None,
+    })
+}
+
+fn hash_with<D: Digest + io::Write>(path: &Path) -> Result<String> {
+    let mut hasher = D::new();
+    io::copy(&mut fs::File::open(path)?, &mut hasher)?;
+
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect())
+}
+
+/// The result of re-checking a downloaded file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileCheck {
+    /// Where the file is (or should be)
+    pub path: PathBuf,
+    /// How the file compares to its checksum (`None` if the file is missing)
+    pub verification: Option<Verification>,
+}
+
+impl FileCheck {
+    /// Whether the file is missing or known to be broken
+    pub fn is_failure(&self) -> bool {
+        self.verification
+            .as_ref()
+            .map_or(true, Verification::is_mismatch)
+    }
+}
+
+/// Re-checks the files of a previously downloaded course against the API, without downloading
+pub async fn verify_course(destination: &Path, course: &Course) -> Result<Vec<FileCheck>> {
+    let mut checks = Vec::new();
+
+    for video in &course.videos {
+        let file_path = download::video_path(destination, video);
+
+        let verification = if file_path.exists() {
+            let checksum = video.checksum.to_owned();
+            let path = file_path.to_owned();
+
+            Some(
+                tokio::task::spawn_blocking(move || verify_file(&path, checksum.as_ref()))
+                    .await??,
+            )
+        } else {
+            None
+        };
+
+        checks.push(FileCheck {
+            path: file_path,
+            verification,
+        });
+    }
+
+    Ok(checks)
+}
+
+impl Display for Verification {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Verification::Match => f.write_str("checksum matches"),
+            Verification::Mismatch { expected, actual } => {
+                write!(f, "checksum mismatch (expected {expected}, got {actual})")
+            }
+            Verification::Missing => f.write_str("no checksum available"),
+            Verification::Unsupported(algorithm) => {
+                write!(f, "unsupported checksum type {algorithm}")
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_verify_file() {
+        let path = std::env::temp_dir().join(format!("tube-get-checksum-{}", std::process::id()));
+        fs::write(&path, "tube-get").unwrap();
+
+        let checksum = |type_field: &str, field: &str| Checksum {
+            type_field: type_field.to_owned(),
+            field: field.to_owned(),
+        };
+
+        assert_eq!(
+            verify_file(
+                &path,
+                Some(&checksum("md5", "52b0de728a136ce5fdb15698ee0d90cc"))
+            )
+            .unwrap(),
+            Verification::Match
+        );
+        assert_eq!(
+            verify_file(&path, Some(&checksum("sha1", "0000"))).unwrap(),
+            Verification::Mismatch {
+                expected: "0000".to_owned(),
+                actual: "b4d29735ac4a39a7d981a36421fe4682e93afa5c".to_owned(),
+            }
+        );
+        assert_eq!(
+            verify_file(
+                &path,
+                Some(&checksum(
+                    "SHA-256",
+                    "8A2D169FFBAEF8271372A169C336BD2F87384BDE9DF376403E799E1C44A6A863"
+                ))
+            )
+            .unwrap(),
+            Verification::Match
+        );
+        assert_eq!(
+            verify_file(&path, Some(&checksum("crc32", "0"))).unwrap(),
+            Verification::Unsupported("crc32".to_owned())
+        );
+        assert_eq!(verify_file(&path, None).unwrap(), Verification::Missing);
+
+        fs::remove_file(&path).unwrap();
+    }
+}
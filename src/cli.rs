@@ -1,64 +1,152 @@
-use crate::constants;
-use anyhow::anyhow;
+use crate::config::Config;
+use crate::output::OutputFormat;
 use clap::{App, Arg, ArgMatches};
-use core::panic;
 use regex::Regex;
-use uuid::Uuid;
+use reqwest::Url;
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use tube_get::constants;
+use tube_get::download::DownloadOptions;
+use tube_get::error::Error;
+use tube_get::extractor::{Quality, TrackSelection};
+use tube_get::feed::FeedOptions;
+use tube_get::filter::Filters;
+use tube_get::playlist::{PlaylistFormat, PlaylistOptions};
+use tube_get::retry::RetryPolicy;
+use tube_get::sanitize::SanitizeMode;
+use tube_get::search::SearchOptions;
+use tube_get::session::Credentials;
+use tube_get::target::Target;
+use tube_get::template::Template;
+
+/// How to log in to the server
+#[derive(Debug, Clone)]
+pub enum Login {
+    /// With a session token copied from the browser
+    Token(String),
+    /// With a username and password, logging in again once the session expires
+    Password(Credentials),
+}
 
 #[derive(Debug, Clone)]
 pub struct CliOptions {
-    pub token: String,
-    pub uuid: Uuid,
-    pub destination: String,
+    pub login: Login,
+    pub server: Url,
+    pub targets: Vec<Target>,
+    pub destination: PathBuf,
+    pub output_template: Template,
+    pub sanitize_mode: SanitizeMode,
     pub no_download: bool,
+    pub format: OutputFormat,
+    pub playlist: Option<PlaylistOptions>,
+    pub verify: bool,
+    pub jobs: usize,
+    pub retry_policy: RetryPolicy,
+    pub track_selection: TrackSelection,
     pub verbosity: u64,
     pub limit_count: Option<u64>,
     pub skip_count: Option<u64>,
+    #[allow(dead_code)]
     pub recursion_limit: Option<u64>,
-    pub file_filter: Option<Regex>,
-    pub path_filter: Option<Regex>,
-    pub file_matcher: Option<Regex>,
-    pub path_matcher: Option<Regex>,
+    pub filters: Filters,
     pub state_store_path: Option<String>,
 }
 
-pub fn configure_parser(default_path: &str) -> App {
+impl CliOptions {
+    /// The options for the download engine
+    pub fn download_options(&self) -> DownloadOptions {
+        DownloadOptions {
+            destination: self.destination.to_owned(),
+            jobs: self.jobs,
+            retry_policy: self.retry_policy,
+            verbosity: self.verbosity,
+            exclusive_folders: self.output_template.has_course_folders(),
+        }
+    }
+}
+
+pub fn configure_parser(default_path: &str) -> App<'_> {
     let app = App::new(constants::NAME)
         .version(constants::VERSION)
         .author(constants::AUTHOR)
         .about(constants::ABOUT)
         .after_help(constants::LICENSE)
+        .args_conflicts_with_subcommands(true)
+        .subcommand_negates_reqs(true)
         .args(&[
-            Arg::with_name("token")
-                .help("Your login token (JSESSIONID)")
-                .required(true)
-                .index(1),
+            token_arg(),
             Arg::with_name("UUID")
-                .help("The UUID of the course you want to crawl & download")
-                .required(true)
+                .help("The UUID(s) or URL(s) of the course(s) or episode(s) you want to crawl & download")
+                .multiple_values(true)
                 .index(2),
+            Arg::with_name("episode")
+                .help("Treat bare UUIDs as episodes (instead of series) and download just those")
+                .short('e')
+                .long("episode"),
+            Arg::with_name("series_file")
+                .help("A file listing series UUIDs or URLs to crawl & download (one per line)")
+                .short('i')
+                .long("input-file")
+                .value_name("path"),
+            username_arg(),
+            server_arg(),
+            config_arg(),
             Arg::with_name("destination")
                 .help("The path to which to write the downloaded files to")
                 .default_value(default_path)
                 .short('d')
                 .long("destination")
                 .value_name("path"),
+            output_template_arg(),
+            sanitize_arg(),
             Arg::with_name("disable download")
-                .help("Crawls without downloading (you mut also use -S)")
+                .help("Crawls without downloading, printing a listing (use -S to remember the videos as pending)")
                 .short('n')
                 .long("no-download"),
-            Arg::with_name("verbosity")
-                .short('v')
-                .multiple(true)
-                .help("The verbosity level of the application"),
+            Arg::with_name("format")
+                .help("The format of the listing printed with -n (json prints an array of courses)")
+                .long("format")
+                .value_name("format")
+                .possible_values(["json", "ndjson", "csv", "table"])
+                .default_value("table"),
+            Arg::with_name("playlist")
+                .help("Writes an M3U (or XSPF, for *.xspf) playlist of the courses in lecture order")
+                .long("playlist")
+                .value_name("path"),
+            Arg::with_name("playlist_format")
+                .help("The format of the playlist (instead of guessing it from the file extension)")
+                .long("playlist-format")
+                .value_name("format")
+                .possible_values(["m3u", "xspf"])
+                .requires("playlist"),
+            Arg::with_name("playlist_remote")
+                .help("Points the playlist at the remote URLs (default with -n) instead of the files")
+                .long("playlist-remote")
+                .requires("playlist"),
+            Arg::with_name("verify")
+                .help("Verifies previously downloaded files against the checksums of the API")
+                .long("verify")
+                .conflicts_with("disable download"),
+            Arg::with_name("jobs")
+                .help("Download up to n videos concurrently")
+                .short('j')
+                .long("jobs")
+                .value_name("integer")
+                .default_value("1"),
+            attempts_arg(),
+            quality_arg(),
+            track_types_arg(),
+            mimetype_arg(),
+            verbosity_arg(),
             Arg::with_name("limit")
-                .help("Limit to n finding(s) to be downloaded")
+                .help("Limit to n episode(s) to be downloaded, in the order they were recorded")
                 .short('l')
                 .long("limit")
                 .value_name("integer")
                 .default_value("0"),
             Arg::with_name("skip")
-                .help("Skip the first n finding(s)")
+                .help("Skip the first n episode(s), in the order they were recorded")
                 .short('s')
                 .long("skip")
                 .value_name("integer")
@@ -83,61 +171,510 @@ pub fn configure_parser(default_path: &str) -> App {
                 .value_name("regex"),
             Arg::with_name("file_matcher")
                 .takes_value(true)
-                .help("Regex filter to exclude non-matching file names")
+                .help("Regex filter to exclude non-matching file names (filters take precedence)")
                 .short('F')
                 .long("file-matcher")
                 .value_name("regex"),
             Arg::with_name("path_matcher")
                 .takes_value(true)
-                .help("Regex filter to exclude non-matching paths names")
+                .help("Regex filter to exclude non-matching paths names (filters take precedence)")
                 .short('P')
                 .long("path-matcher")
                 .value_name("regex"),
             Arg::with_name("state_store")
                 .takes_value(true)
-                .help("Store progress in a file (and resume when possible)")
+                .help("Store progress in a file, skipping videos it lists as downloaded (even if moved)")
                 .short('S')
                 .long("store-state")
                 .value_name("path"),
-        ]);
+        ])
+        .subcommand(
+            App::new("search")
+                .about("Searches for series by title, lecturer or term")
+                .args(&[
+                    token_arg(),
+                    Arg::with_name("query")
+                        .help("Only list series whose title contains this text")
+                        .index(2),
+                    Arg::with_name("lecturer")
+                        .help("Only list series by lecturers whose name contains this text")
+                        .short('L')
+                        .long("lecturer")
+                        .value_name("name"),
+                    Arg::with_name("term")
+                        .help("Only list series of a term (e.g. \"WS 2021\" or \"2022\")")
+                        .short('T')
+                        .long("term")
+                        .value_name("term"),
+                    username_arg(),
+                    server_arg(),
+                    config_arg(),
+                    attempts_arg(),
+                    verbosity_arg(),
+                ]),
+        )
+        .subcommand(
+            App::new("feed")
+                .about("Writes a series as an RSS podcast feed")
+                .args(&[
+                    token_arg(),
+                    Arg::with_name("series")
+                        .help("The UUID or URL of the series")
+                        .index(2),
+                    Arg::with_name("output")
+                        .help("The file to write the feed to [default: stdout]")
+                        .short('o')
+                        .long("output")
+                        .value_name("path"),
+                    Arg::with_name("base_url")
+                        .help("Points the enclosures at the downloaded files, served below this URL")
+                        .long("base-url")
+                        .value_name("url"),
+                    output_template_arg(),
+                    sanitize_arg(),
+                    username_arg(),
+                    server_arg(),
+                    config_arg(),
+                    attempts_arg(),
+                    quality_arg(),
+                    track_types_arg(),
+                    mimetype_arg(),
+                    verbosity_arg(),
+                ]),
+        );
 
     app
 }
 
-pub fn get_options<'a>(matches: &'a ArgMatches) -> Result<CliOptions, anyhow::Error> {
-    let make_regex = |name: &str| {
-        matches.value_of(name).and_then(|v| match Regex::new(v) {
-            Ok(regex) => Some(regex),
-            Err(err) => panic!("{}", &format!("{:?}", err)),
+fn token_arg<'a>() -> Arg<'a> {
+    // Not required by clap, as it's left out when logging in with a password (see `get_login`)
+    Arg::with_name("token")
+        .help(concat!(
+            "Your login token (JSESSIONID), left out when logging in with a username ",
+            "(one from the environment or config file is used unless a target follows the token)"
+        ))
+        .index(1)
+}
+
+fn username_arg<'a>() -> Arg<'a> {
+    Arg::with_name("username")
+        .help(concat!(
+            "Logs in with a username & password instead of a token ",
+            "[env: TUBE_GET_USERNAME, TUBE_GET_PASSWORD; otherwise the password is asked for]"
+        ))
+        .long("username")
+        .value_name("name")
+}
+
+fn server_arg<'a>() -> Arg<'a> {
+    Arg::with_name("server")
+        .help("The base URL of the Opencast server [default: https://tube.tugraz.at]")
+        .long("server")
+        .value_name("url")
+}
+
+fn config_arg<'a>() -> Arg<'a> {
+    Arg::with_name("config")
+        .help("The configuration file to use [default: ~/.config/tube-get/config.toml]")
+        .short('c')
+        .long("config")
+        .value_name("path")
+}
+
+fn attempts_arg<'a>() -> Arg<'a> {
+    Arg::with_name("attempts")
+        .help("Maximum number of attempts per request (retrying with exponential backoff)")
+        .short('a')
+        .long("attempts")
+        .value_name("integer")
+        .default_value("5")
+}
+
+fn output_template_arg<'a>() -> Arg<'a> {
+    Arg::with_name("output_template")
+        .help(concat!(
+            "The path of each file below the destination, with the placeholders {series}, ",
+            "{series_id}, {title}, {id}, {track_id}, {date[:format]}, {index[:width]}, ",
+            "{duration}, {presenter}, {type}, {flavor}, {quality} and {ext}"
+        ))
+        .long("output-template")
+        .value_name("template")
+        .default_value(constants::DEFAULT_TEMPLATE)
+}
+
+fn sanitize_arg<'a>() -> Arg<'a> {
+    Arg::with_name("sanitize")
+        .help("Which file systems the file names must be valid on (use windows for SMB shares)")
+        .long("sanitize")
+        .value_name("mode")
+        .possible_values(["posix", "windows"])
+        .default_value(match SanitizeMode::native() {
+            SanitizeMode::Posix => "posix",
+            SanitizeMode::Windows => "windows",
         })
+}
+
+fn quality_arg<'a>() -> Arg<'a> {
+    Arg::with_name("quality")
+        .help("The video quality to download (falls back to the next-best one)")
+        .short('q')
+        .long("quality")
+        .value_name("quality")
+        .possible_values(["high", "medium", "low", "best", "smallest"])
+        .default_value("high")
+}
+
+fn track_types_arg<'a>() -> Arg<'a> {
+    Arg::with_name("track_types")
+        .help("Only download these track types (comma-separated)")
+        .short('t')
+        .long("track-type")
+        .value_name("type")
+        .possible_values(["presenter", "presentation", "presenter_no_audio", "raw"])
+        .multiple_values(true)
+        .use_value_delimiter(true)
+}
+
+fn mimetype_arg<'a>() -> Arg<'a> {
+    Arg::with_name("mimetype")
+        .help("The mimetype of the tracks to download")
+        .short('m')
+        .long("mimetype")
+        .value_name("mimetype")
+        .default_value(constants::MP4_MIME)
+}
+
+fn verbosity_arg<'a>() -> Arg<'a> {
+    Arg::with_name("verbosity")
+        .short('v')
+        .multiple_occurrences(true)
+        .help("The verbosity level of the application")
+}
+
+pub fn get_options(
+    matches: &ArgMatches,
+    config: &Config,
+    login: Login,
+) -> Result<CliOptions, anyhow::Error> {
+    let make_regex = |name: &str| {
+        matches
+            .value_of(name)
+            .map(|v| Regex::new(v).map_err(|err| Error::Input(format!("Invalid regex {}", err))))
+            .transpose()
     };
 
     Ok(CliOptions {
-        token: matches
-            .value_of("token")
-            .ok_or_else(|| anyhow!("Missing token"))?
-            .to_owned(),
-        uuid: matches.value_of("UUID").unwrap().parse()?,
-        destination: matches.value_of("destination").unwrap().to_owned(),
+        targets: get_targets(matches, &login)?,
+        login,
+        server: get_server(matches, config)?,
+        destination: PathBuf::from(matches.value_of("destination").unwrap()),
+        output_template: matches.value_of("output_template").unwrap().parse()?,
+        sanitize_mode: matches.value_of("sanitize").unwrap().parse()?,
         no_download: matches.is_present("disable download"),
+        format: matches.value_of("format").unwrap().parse()?,
+        playlist: get_playlist_options(matches)?,
+        verify: matches.is_present("verify"),
+        jobs: get_number(matches, "jobs")?.unwrap_or(1) as usize,
+        retry_policy: get_retry_policy(matches)?,
+        track_selection: get_track_selection(matches)?,
         verbosity: matches.occurrences_of("verbosity"),
-        limit_count: make_option(matches.value_of("limit").unwrap().parse::<u64>()),
-        skip_count: make_option(matches.value_of("skip").unwrap().parse::<u64>()),
-        recursion_limit: make_option(matches.value_of("max_depth").unwrap().parse::<u64>()),
-        file_filter: make_regex("file_filter"),
-        path_filter: make_regex("path_filter"),
-        file_matcher: make_regex("file_matcher"),
-        path_matcher: make_regex("path_matcher"),
+        limit_count: get_number(matches, "limit")?,
+        skip_count: get_number(matches, "skip")?,
+        recursion_limit: get_number(matches, "max_depth")?,
+        filters: Filters {
+            file_filter: make_regex("file_filter")?,
+            path_filter: make_regex("path_filter")?,
+            file_matcher: make_regex("file_matcher")?,
+            path_matcher: make_regex("path_matcher")?,
+        },
         state_store_path: matches.value_of("state_store").map(|path| path.to_owned()),
     })
 }
 
-/// Converts a number (which has to be greater than zero) to an option, or None (in case of zero)
-fn make_option(number: Result<u64, std::num::ParseIntError>) -> Option<u64> {
-    match number
-        .unwrap_or_else(|err| panic!("Invalid number (must be a positive integer): {:?}", err))
+pub fn get_search_options(
+    matches: &ArgMatches,
+    config: &Config,
+    login: &Login,
+) -> Result<SearchOptions, anyhow::Error> {
+    Ok(SearchOptions {
+        server: get_server(matches, config)?,
+        query: positionals(matches, "query", login)
+            .first()
+            .map(|&query| query.to_owned()),
+        lecturer: matches.value_of("lecturer").map(str::to_owned),
+        term: matches.value_of("term").map(str::to_owned),
+        retry_policy: get_retry_policy(matches)?,
+        verbosity: matches.occurrences_of("verbosity"),
+    })
+}
+
+pub fn get_feed_options(
+    matches: &ArgMatches,
+    config: &Config,
+    login: &Login,
+) -> Result<FeedOptions, anyhow::Error> {
+    let series = *positionals(matches, "series", login)
+        .first()
+        .ok_or_else(|| Error::Input("Missing series".to_owned()))?;
+
+    Ok(FeedOptions {
+        server: get_server(matches, config)?,
+        target: Target::parse_as(series, Target::Series)?,
+        base_url: matches
+            .value_of("base_url")
+            .map(|url| {
+                Url::parse(url)
+                    .map_err(|err| Error::Input(format!("Invalid base URL {}: {}", url, err)))
+            })
+            .transpose()?,
+        output_template: matches.value_of("output_template").unwrap().parse()?,
+        sanitize_mode: matches.value_of("sanitize").unwrap().parse()?,
+        track_selection: get_track_selection(matches)?,
+        retry_policy: get_retry_policy(matches)?,
+        verbosity: matches.occurrences_of("verbosity"),
+    })
+}
+
+/// Logs in with a username and password (from the environment, the config file or a prompt),
+/// or else with the token given as the first positional argument.
+///
+/// `--username` always logs in with a password. So does a username from the environment or the
+/// config file, unless there are both a token and `targets` (e.g. the query of a search): a single
+/// positional argument is taken as the target then, and as the token otherwise (which fails if
+/// a target is `required`).
+pub fn get_login(
+    matches: &ArgMatches,
+    config: &Config,
+    targets: &str,
+    required: bool,
+) -> Result<Login, anyhow::Error> {
+    // Clap puts the first positional argument into `token`, even if it's the only target
+    let has_target = matches.is_present(targets);
+    let has_token = matches.is_present("token") && (has_target || !required);
+
+    let username = match matches.value_of("username") {
+        Some(username) => Some(username.to_owned()),
+        None if !(has_token && has_target) => env::var(constants::USERNAME_VAR)
+            .ok()
+            .or_else(|| config.username.to_owned()),
+        None => None,
+    };
+
+    let username = match username {
+        Some(username) => username,
+        None => {
+            let token = matches.value_of("token").filter(|_| has_token);
+            let token = token.ok_or_else(|| {
+                Error::Input("Missing token (or log in with --username instead)".to_owned())
+            })?;
+
+            return Ok(Login::Token(token.to_owned()));
+        }
+    };
+
+    let password = match env::var(constants::PASSWORD_VAR)
+        .ok()
+        .or_else(|| config.password.to_owned())
     {
-        0 => None,
-        n => Some(n),
+        Some(password) => password,
+        None => {
+            rpassword::prompt_password(format!("Password for {}: ", username)).map_err(|err| {
+                Error::Input(format!(
+                    "Could not read the password ({}), please set {}",
+                    err,
+                    constants::PASSWORD_VAR
+                ))
+            })?
+        }
+    };
+
+    Ok(Login::Password(Credentials { username, password }))
+}
+
+/// The values of a positional argument, in front of which clap puts the first positional value
+/// into `token` when logging in with a password (see `get_login`)
+fn positionals<'a>(matches: &'a ArgMatches, name: &str, login: &Login) -> Vec<&'a str> {
+    let shifted = match login {
+        Login::Token(_) => None,
+        Login::Password(_) => matches.value_of("token"),
+    };
+
+    shifted
+        .into_iter()
+        .chain(matches.values_of(name).into_iter().flatten())
+        .collect()
+}
+
+fn get_server(matches: &ArgMatches, config: &Config) -> Result<Url, anyhow::Error> {
+    parse_server(
+        matches
+            .value_of("server")
+            .or(config.server.as_deref())
+            .unwrap_or(constants::BASE_URL),
+    )
+}
+
+fn get_retry_policy(matches: &ArgMatches) -> Result<RetryPolicy, Error> {
+    Ok(RetryPolicy {
+        max_attempts: get_number(matches, "attempts")?.unwrap_or(1) as u32,
+    })
+}
+
+fn get_track_selection(matches: &ArgMatches) -> Result<TrackSelection, anyhow::Error> {
+    Ok(TrackSelection {
+        quality: matches.value_of("quality").unwrap().parse::<Quality>()?,
+        track_types: matches
+            .values_of("track_types")
+            .map(|types| types.map(str::parse).collect::<Result<_, _>>())
+            .transpose()?,
+        mimetype: matches.value_of("mimetype").unwrap().to_owned(),
+    })
+}
+
+fn get_playlist_options(matches: &ArgMatches) -> Result<Option<PlaylistOptions>, anyhow::Error> {
+    let path = match matches.value_of("playlist") {
+        Some(path) => PathBuf::from(path),
+        None => return Ok(None),
+    };
+
+    Ok(Some(PlaylistOptions {
+        format: match matches.value_of("playlist_format") {
+            Some(format) => format.parse()?,
+            None => PlaylistFormat::from_path(&path),
+        },
+        path,
+        // Without downloading, there are no files to point at
+        remote: matches.is_present("playlist_remote") || matches.is_present("disable download"),
+    }))
+}
+
+/// Collects the series (and episodes) from the command line and the series file (if given)
+fn get_targets(matches: &ArgMatches, login: &Login) -> Result<Vec<Target>, anyhow::Error> {
+    let mut targets = positionals(matches, "UUID", login)
+        .into_iter()
+        .map(str::to_owned)
+        .collect::<Vec<_>>();
+
+    if let Some(path) = matches.value_of("series_file") {
+        let text = fs::read_to_string(path)
+            .map_err(|err| Error::filesystem("read the series file", path, err))?;
+
+        // Skip empty lines and comments
+        targets.extend(
+            text.lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                .map(str::to_owned),
+        );
+    }
+
+    // Bare UUIDs are series, unless we were told they are episodes
+    let bare = if matches.is_present("episode") {
+        Target::Episode
+    } else {
+        Target::Series
+    };
+
+    if targets.is_empty() {
+        return Err(Error::Input("Missing the UUID(s) or URL(s) to crawl".to_owned()).into());
+    }
+
+    let mut parsed: Vec<Target> = Vec::new();
+
+    for target in targets {
+        let target = Target::parse_as(&target, bare)?;
+
+        // Download each series (or episode) only once
+        if !parsed.contains(&target) {
+            parsed.push(target);
+        }
+    }
+
+    Ok(parsed)
+}
+
+/// Parses a number option (which has to be a positive integer), turning zero into `None`
+fn get_number(matches: &ArgMatches, name: &str) -> Result<Option<u64>, Error> {
+    let value = matches.value_of(name).unwrap_or("0");
+
+    match value.parse::<u64>() {
+        Ok(0) => Ok(None),
+        Ok(n) => Ok(Some(n)),
+        Err(_) => Err(Error::Input(format!(
+            "Invalid number {} (must be a positive integer)",
+            value
+        ))),
+    }
+}
+
+/// Parses the base URL of the server, making sure further paths can be joined onto it
+fn parse_server(server: &str) -> Result<Url, anyhow::Error> {
+    let mut url = Url::parse(server)
+        .map_err(|err| Error::Input(format!("Invalid server URL {}: {}", server, err)))?;
+
+    if !url.path().ends_with('/') {
+        url.set_path(&format!("{}/", url.path()));
+    }
+
+    Ok(url)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_get_login() {
+        let config = Config {
+            username: Some("alice".to_owned()),
+            password: Some("secret".to_owned()),
+            ..Default::default()
+        };
+        let matches = |args: &[&str]| {
+            configure_parser(".")
+                .try_get_matches_from([constants::NAME].iter().chain(args))
+                .unwrap()
+        };
+        let login = |matches: &ArgMatches, config: &Config| {
+            let targets_required = !matches.is_present("series_file");
+            get_login(matches, config, "UUID", targets_required)
+        };
+
+        // A username from the config file doesn't turn the token into a target
+        let args = matches(&["token", "series"]);
+        let token = login(&args, &config).unwrap();
+        assert!(matches!(&token, Login::Token(token) if token == "token"));
+        assert_eq!(positionals(&args, "UUID", &token), ["series"]);
+
+        let args = matches(&["series"]);
+        let password = login(&args, &config).unwrap();
+        assert!(matches!(password, Login::Password(_)));
+        assert_eq!(positionals(&args, "UUID", &password), ["series"]);
+        assert!(login(&args, &Config::default()).is_err());
+
+        // ... but `--username` does
+        let args = matches(&["--username", "bob", "first", "second"]);
+        let password = login(&args, &config).unwrap();
+        assert!(matches!(&password, Login::Password(credentials) if credentials.username == "bob"));
+        assert_eq!(positionals(&args, "UUID", &password), ["first", "second"]);
+
+        // The only positional argument of a search is the query if there is a username ...
+        let args = matches(&["search", "Analysis"]);
+        let (_, args) = args.subcommand().unwrap();
+        let password = get_login(args, &config, "query", false).unwrap();
+        assert!(matches!(password, Login::Password(_)));
+        assert_eq!(positionals(args, "query", &password), ["Analysis"]);
+
+        // ... and the token otherwise
+        let token = get_login(args, &Config::default(), "query", false);
+        if env::var(constants::USERNAME_VAR).is_err() {
+            assert!(matches!(token.unwrap(), Login::Token(token) if token == "Analysis"));
+        }
+
+        let args = matches(&["search", "token", "Analysis"]);
+        let (_, args) = args.subcommand().unwrap();
+        let token = get_login(args, &config, "query", false).unwrap();
+        assert!(matches!(&token, Login::Token(token) if token == "token"));
+        assert_eq!(positionals(args, "query", &token), ["Analysis"]);
     }
 }
@@ -0,0 +1,51 @@
+use anyhow::{Context, Result};
+use serde::Deserialize;
+
+use std::fs;
+use std::path::PathBuf;
+
+use tube_get::constants;
+use tube_get::error::Error;
+
+/// Settings read from the configuration file (command line options take precedence)
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Config {
+    /// The base URL of the Opencast server
+    pub server: Option<String>,
+    /// The username to log in with (instead of a token)
+    pub username: Option<String>,
+    /// The password to log in with (asked for if it's missing)
+    pub password: Option<String>,
+}
+
+impl Config {
+    /// Loads the configuration from the given file, or from the default location
+    /// (e.g. `~/.config/tube-get/config.toml`) if it exists
+    pub fn load(path: Option<&str>) -> Result<Self> {
+        let path = match path {
+            Some(path) => PathBuf::from(path),
+            None => match default_path() {
+                Some(path) if path.exists() => path,
+                _ => return Ok(Config::default()),
+            },
+        };
+
+        let text = fs::read_to_string(&path)
+            .with_context(|| format!("Could not read the config file {}", path.display()))?;
+
+        toml::from_str(&text).map_err(|err| {
+            Error::Input(format!(
+                "Could not parse the config file {}: {}",
+                path.display(),
+                err
+            ))
+            .into()
+        })
+    }
+}
+
+/// The default location of the configuration file
+pub fn default_path() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join(constants::NAME).join(constants::CONFIG_FILE_NAME))
+}
@@ -1,4 +1,6 @@
-// The name of the application
+//! Names, defaults and limits shared by the library and the command line interface.
+
+/// The name of the application
 pub const NAME: &str = "tube-get";
 
 /// The main author of the application
@@ -9,7 +11,7 @@ pub const VERSION: &str = env!("CARGO_PKG_VERSION");
 
 /// Describes the application (i.e. its use cases) in a short phrase
 pub const ABOUT: &str =
-    "A Rust tool for recursively crawling & downloading videos from TU Graz TUbe";
+    "A Rust tool for recursively crawling & downloading videos from TU Graz TUbe (and other Opencast servers)";
 
 /// The licence notice (AGPL 3) of the application
 pub const LICENSE: &str = concat![
@@ -17,10 +19,53 @@ pub const LICENSE: &str = concat![
     "Licensed under the AGPL 3.0 <https://www.gnu.org/licenses/agpl-3.0.en.html>"
 ];
 
-/// The base URL
+/// The default base URL of the Opencast server
 pub const BASE_URL: &str = "https://tube.tugraz.at";
 
+/// The name of the configuration file (in the config directory of the user)
+pub const CONFIG_FILE_NAME: &str = "config.toml";
+
+/// The number of episodes to request per page of the search API
+pub const PAGE_SIZE: u64 = 100;
+
+/// The maximum number of search results to count the episodes of (as that takes a request each)
+pub const MAX_EPISODE_COUNTS: usize = 20;
+
+/// How often to download a file again if its checksum doesn't match
+pub const CHECKSUM_RETRIES: u32 = 2;
+
+/// The name of the session cookie
+pub const SESSION_COOKIE: &str = "JSESSIONID";
+
+/// The form login of Opencast (below the base URL)
+pub const LOGIN_PATH: &str = "j_spring_security_check";
+
+/// The API telling whom a session belongs to (below the base URL)
+pub const ME_PATH: &str = "info/me.json";
+
+/// The role of users who aren't logged in
+pub const ANONYMOUS_ROLE: &str = "ROLE_ANONYMOUS";
+
+/// The environment variable the username for the login is read from
+pub const USERNAME_VAR: &str = "TUBE_GET_USERNAME";
+/// The environment variable the password for the login is read from
+pub const PASSWORD_VAR: &str = "TUBE_GET_PASSWORD";
+
+/// How often to renew an expired session before giving up on a request
+pub const MAX_RENEWALS: u32 = 3;
+
+/// The default template for the paths of the downloaded files
+pub const DEFAULT_TEMPLATE: &str = "{series}/{title}_{type}.{ext}";
+
 /// The mp4 mimetype
 pub const MP4_MIME: &str = "video/mp4";
 
+/// The tag of high-quality tracks
 pub const HIGH_QUALITY: &str = "high";
+/// The tag of medium-quality tracks
+pub const MEDIUM_QUALITY: &str = "medium";
+/// The tag of low-quality tracks
+pub const LOW_QUALITY: &str = "low";
+
+/// All known quality tags, from highest to lowest
+pub const QUALITIES: [&str; 3] = [HIGH_QUALITY, MEDIUM_QUALITY, LOW_QUALITY];
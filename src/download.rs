@@ -1,115 +1,596 @@
-use anyhow::Result;
-use indicatif::{HumanBytes, HumanDuration, MultiProgress, ProgressBar, ProgressStyle};
+//! The download engine: resumable, verified and (optionally) concurrent downloads of courses.
+
+use anyhow::{anyhow, Result};
 use io::Write;
-use reqwest::Client;
+use reqwest::header::{CONTENT_RANGE, RANGE};
+use reqwest::{Response, StatusCode};
+use tokio::sync::Semaphore;
 
+use std::collections::HashSet;
 use std::fmt::Display;
 use std::fs;
 use std::future::Future;
 use std::io;
-use std::path::PathBuf;
-use std::sync::Arc;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
+use crate::checksum::{self, Verification};
+use crate::constants;
+use crate::error::{self, Error};
+use crate::extractor::{Course, Video};
+use crate::retry::{self, RetryAfter, RetryPolicy};
+use crate::session::Session;
+use crate::state::{StateStore, TrackState};
 use crate::types::episodes::TrackType;
-use crate::{cli::CliOptions, extractor::Course};
 
+/// The options of the download engine
+#[derive(Debug, Clone)]
+pub struct DownloadOptions {
+    /// The folder the paths of the videos are relative to
+    pub destination: PathBuf,
+    /// The maximum number of concurrent downloads
+    pub jobs: usize,
+    /// How often to retry the requests of a download
+    pub retry_policy: RetryPolicy,
+    /// How many details to report (0 is the least)
+    pub verbosity: u64,
+    /// Whether every course has a folder of its own, so unknown `.part` files in it can be removed
+    pub exclusive_folders: bool,
+}
+
+/// Gets told about the progress of the downloads (e.g. to render progress bars).
+///
+/// All methods do nothing by default. They are called from several tasks at once.
+#[allow(unused_variables)]
+pub trait DownloadObserver: Send + Sync {
+    /// The download of a course started
+    fn course_started(&self, course: &Course) {}
+
+    /// A message about a retry, a resumed download or similar which may be shown to the user
+    fn message(&self, message: &str) {}
+
+    /// The transfer of a video started at `offset` (when resuming), with its total size (if known)
+    fn transfer_started(&self, video: &Video, offset: u64, size: Option<u64>) {}
+
+    /// Another chunk of `bytes` bytes of a video was written
+    fn transfer_progress(&self, video: &Video, bytes: u64) {}
+
+    /// The transfer of a video ended (successfully or not)
+    fn transfer_finished(&self, video: &Video) {}
+
+    /// A video was downloaded, or skipped (`None`) since it was downloaded in a previous run
+    fn video_finished(&self, video: &Video, downloaded: Option<&Downloaded>) {}
+
+    /// All downloads of a course ended, with the titles of the failed videos and their errors
+    fn course_finished(&self, course: &Course, failures: &[(String, anyhow::Error)]) {}
+}
+
+/// An observer which ignores the progress
+pub struct Silent;
+
+impl DownloadObserver for Silent {}
+
+/// Downloads the videos of a course, reporting the progress to the observer.
+///
+/// The videos are downloaded to their paths below the destination, resuming partial downloads
+/// of previous runs. Fails if any of the videos could not be downloaded.
 pub fn download_course<'a>(
-    cli_options: CliOptions,
+    options: DownloadOptions,
     course: Course,
-    multi_bar: Arc<MultiProgress>,
-    client: &Client,
+    state_store: Option<StateStore>,
+    observer: Arc<dyn DownloadObserver>,
+    session: &Session,
 ) -> impl Future<Output = Result<()>> + 'a {
-    let main_pb = multi_bar.add(ProgressBar::new(course.videos.len() as u64));
+    let file_paths = course
+        .videos
+        .iter()
+        .map(|video| video_path(&options.destination, video))
+        .collect::<Vec<_>>();
 
-    main_pb.set_style(
-        ProgressStyle::default_bar().template(
-            &(String::new() + "[{elapsed_precise}] {wide_bar} Total progress: {pos}/{len}"),
-        ),
-    );
+    // Limits the number of concurrent downloads
+    let semaphore = Arc::new(Semaphore::new(options.jobs));
 
-    main_pb.println(format!("Downloading course {}", course.title));
+    let state_store = Arc::new(Mutex::new(state_store));
+    let options = Arc::new(options);
 
-    // Make the main progress bar render immediately rather than waiting for the first task to finish.
-    main_pb.tick();
+    let session = session.clone();
+    async move {
+        observer.course_started(&course);
 
-    let folder_path = PathBuf::from(cli_options.destination.to_owned()).join(course.title);
+        let folder_paths = file_paths
+            .iter()
+            .filter_map(|path| path.parent())
+            .collect::<HashSet<_>>();
 
-    // TODO Change this to a question mark operator somehow
-    fs::create_dir_all(&folder_path).expect("Could not create directory");
+        let part_paths = file_paths.iter().map(|path| part_path(path)).collect();
 
-    let mut count = cli_options.skip_count.unwrap_or(0) as usize;
+        // Only remove unknown files if we look at the whole course, and nothing else shares its folder
+        let complete_course = !course.partial && options.exclusive_folders;
 
-    let client = client.clone();
-    async move {
-        let client = client;
-        for video in course.videos.iter() {
-            let file = fs::OpenOptions::new()
-                .create(true)
-                .write(true)
-                .append(false)
-                .open(folder_path.clone().join(create_video_file_name(video)))?;
+        for folder_path in folder_paths {
+            fs::create_dir_all(folder_path)
+                .map_err(|err| Error::filesystem("create the folder", folder_path, err))?;
+            remove_stale_part_files(folder_path, &part_paths, complete_course, &*observer)?;
+        }
 
-            let request = client.get(&video.url);
+        let mut handles = Vec::new();
 
-            let mut response = request.send().await?;
-            let mut writer = io::BufWriter::new(file);
+        for (video, file_path) in course.videos.iter().cloned().zip(file_paths) {
+            let session = session.clone();
+            let options = Arc::clone(&options);
+            let observer = Arc::clone(&observer);
+            let state_store = Arc::clone(&state_store);
+            let semaphore = Arc::clone(&semaphore);
 
-            let content_length = response.content_length().unwrap_or(0);
+            handles.push(tokio::spawn(async move {
+                let _permit = semaphore
+                    .acquire()
+                    .await
+                    .map_err(|err| (video.title.to_owned(), err.into()))?;
 
-            if cli_options.verbosity >= 1 {
-                main_pb.println(format!("Downloading URL: {}", &video.url));
+                let outcome = download_tracked(
+                    &session,
+                    &video,
+                    &file_path,
+                    &options,
+                    &*observer,
+                    &state_store,
+                )
+                .await
+                .map_err(|err| (video.title.to_owned(), err))?;
+
+                observer.video_finished(&video, outcome.as_ref());
+                Ok(())
+            }));
+        }
+
+        // Wait for all downloads, collecting the failed ones
+        let mut failures = Vec::new();
+        for handle in handles {
+            if let Err(failure) = handle.await? {
+                failures.push(failure);
             }
+        }
+
+        observer.course_finished(&course, &failures);
+
+        if failures.is_empty() {
+            Ok(())
+        } else {
+            Err(error::combine(
+                format!("{} video(s) could not be downloaded", failures.len()),
+                failures.into_iter().map(|(_, err)| err).collect(),
+            ))
+        }
+    }
+}
+
+/// Downloads a video while keeping track of its state (if a state store is used).
+///
+/// Returns `None` if the video was skipped since it was downloaded completely in a previous run,
+/// either as its file exists or as the state store says so (e.g. if the file was moved since).
+/// Existing files only count as downloaded in the store if their checksum matches.
+async fn download_tracked(
+    session: &Session,
+    video: &Video,
+    file_path: &Path,
+    options: &DownloadOptions,
+    observer: &dyn DownloadObserver,
+    state_store: &Mutex<Option<StateStore>>,
+) -> Result<Option<Downloaded>> {
+    let part_path = part_path(file_path);
 
-            if cli_options.verbosity >= 2 {
-                main_pb.println(format!("Status code: {:#?}", &response.status()));
-                main_pb.println(format!("Headers:\n{:#?}", &response.headers()));
+    let previous_state = state_store
+        .lock()
+        .unwrap()
+        .as_ref()
+        .and_then(|store| store.get(video).cloned());
+
+    match previous_state {
+        Some(TrackState::Completed { .. }) => {
+            if options.verbosity >= 1 && !file_path.exists() {
+                observer.message(&format!(
+                    "  {}: downloaded in a previous run, skipping it",
+                    video.title
+                ));
             }
 
-            let progress_bar = multi_bar.add(
-                ProgressBar::new(content_length)
-                    .with_style(
-                        ProgressStyle::default_bar()
-                            .template(
-                                "[{elapsed_precise}] {wide_bar:.cyan} {bytes}/{total_bytes} ({percent}%) - ETA {eta_precise} with {binary_bytes_per_sec} - {msg}"
-                            )
-                            .progress_chars("█▉▊▋▌▍▎▏  "),
-                    )
-                    .with_message(video.title.to_owned()),
-            );
-
-            while let Some(chunk) = response.chunk().await? {
-                progress_bar.inc(chunk.len() as u64); // Increase ProgressBar by chunk size
-                main_pb.tick();
-                writer.write_all(&chunk)?; // Write chunk to output file
+            return Ok(None);
+        }
+        Some(TrackState::Failed { error }) => {
+            observer.message(&format!(
+                "  {}: failed in a previous run ({}), trying again",
+                video.title, error
+            ));
+        }
+        _ => {}
+    }
+
+    // Files are only renamed to their final name once they are complete, so existing files were
+    // downloaded completely in a previous run. As the store doesn't know this one, make sure it
+    // is this video (and not e.g. another episode with the same name) before counting it as done.
+    if let Ok(metadata) = fs::metadata(file_path) {
+        let checksum = video.checksum.to_owned();
+        let path = file_path.to_owned();
+        let verification =
+            tokio::task::spawn_blocking(move || checksum::verify_file(&path, checksum.as_ref()))
+                .await??;
+
+        match verification {
+            Verification::Match => {
+                if let Some(store) = state_store.lock().unwrap().as_mut() {
+                    store.set(
+                        video,
+                        TrackState::Completed {
+                            bytes: metadata.len(),
+                        },
+                    );
+                    store.save()?;
+                }
+            }
+            // Never overwrite a file we didn't download
+            Verification::Mismatch { .. } => {
+                return Err(anyhow!(
+                    "{}: {} already exists, but is a different file ({})",
+                    video.title,
+                    file_path.display(),
+                    verification
+                ));
             }
+            // Nothing to tell the files apart, so keep the file without vouching for it
+            Verification::Missing | Verification::Unsupported(_) => {
+                if options.verbosity >= 1 {
+                    observer.message(&format!(
+                        "  {}: {} already exists, skipping it",
+                        video.title,
+                        file_path.display()
+                    ));
+                }
+            }
+        }
 
-            let elapsed = HumanDuration(progress_bar.elapsed());
-            let downloaded = HumanBytes(progress_bar.position());
+        return Ok(None);
+    }
 
-            progress_bar.finish_and_clear();
+    if let Some(store) = state_store.lock().unwrap().as_mut() {
+        let bytes = fs::metadata(&part_path).map(|m| m.len()).unwrap_or(0);
+        store.set(video, TrackState::Partial { bytes });
+        store.save()?;
+    }
 
-            writer.flush()?;
+    let result = download_verified(session, video, file_path, options, observer).await;
 
-            count += 1;
-            main_pb.println(format!(
-                "  Finished {:2}/{:2}, {downloaded} in {elapsed}: {}",
-                count,
-                course.videos.len() + cli_options.skip_count.unwrap_or(0) as usize,
-                video.title
-            ));
+    if let Some(store) = state_store.lock().unwrap().as_mut() {
+        store.set(
+            video,
+            match &result {
+                Ok(downloaded) => TrackState::Completed {
+                    bytes: downloaded.size,
+                },
+                Err(err) => match fs::metadata(&part_path).map(|m| m.len()) {
+                    Ok(bytes) if bytes > 0 => TrackState::Partial { bytes },
+                    _ => TrackState::Failed {
+                        error: err.to_string(),
+                    },
+                },
+            },
+        );
+        store.save()?;
+    }
+
+    result.map(Some)
+}
+
+/// Downloads a video into a `.part` file and verifies its checksum (if the API advertised one),
+/// then renames it to its final name.
+///
+/// Corrupt files are moved aside (to `<file>.corrupt`) and downloaded again.
+async fn download_verified(
+    session: &Session,
+    video: &Video,
+    file_path: &Path,
+    options: &DownloadOptions,
+    observer: &dyn DownloadObserver,
+) -> Result<Downloaded> {
+    let part_path = part_path(file_path);
+    let mut attempt = 0;
+
+    loop {
+        attempt += 1;
+
+        let downloaded = download_resumed(session, video, &part_path, options, observer).await?;
+
+        // Hashing multi-GB files takes a while, so don't block the other downloads
+        let checksum = video.checksum.to_owned();
+        let path = part_path.to_owned();
+        let verification =
+            tokio::task::spawn_blocking(move || checksum::verify_file(&path, checksum.as_ref()))
+                .await??;
+
+        if !verification.is_mismatch() {
+            if options.verbosity >= 1 {
+                observer.message(&format!("  {}: {}", video.title, verification));
+            }
 
-            main_pb.inc(1);
+            fs::rename(&part_path, file_path)?;
+            return Ok(downloaded);
         }
 
-        main_pb.finish();
+        let mut corrupt_path = file_path.as_os_str().to_owned();
+        corrupt_path.push(".corrupt");
+        fs::rename(&part_path, &corrupt_path)?;
 
-        println!("Download complete.");
-        Ok(())
+        observer.message(&format!(
+            "  {}: {}, moved to {}",
+            video.title,
+            verification,
+            Path::new(&corrupt_path).display()
+        ));
+
+        if attempt > constants::CHECKSUM_RETRIES {
+            return Err(anyhow!("{}: {}", video.title, verification));
+        }
+    }
+}
+
+/// Downloads a video, resuming the download (after a backoff) on transient errors,
+/// and (right away) once the session was renewed.
+///
+/// This is the only place the requests of a download are retried, so they are sent at most
+/// `max_attempts` times (not counting the renewals of the session).
+async fn download_resumed(
+    session: &Session,
+    video: &Video,
+    part_path: &Path,
+    options: &DownloadOptions,
+    observer: &dyn DownloadObserver,
+) -> Result<Downloaded> {
+    let policy = options.retry_policy;
+    let mut attempt = 1;
+
+    loop {
+        let download = || download_video(session, video, part_path, options, observer);
+
+        match session.with_renewal(download).await {
+            Err(err) if attempt < policy.max_attempts && retry::is_transient(&err) => {
+                let delay = retry::requested_delay(&err).unwrap_or_else(|| policy.delay(attempt));
+                attempt += 1;
+
+                observer.message(&format!(
+                    "  {}: {}, resuming in {:.1}s (attempt {}/{})",
+                    video.title,
+                    err,
+                    delay.as_secs_f64(),
+                    attempt,
+                    policy.max_attempts
+                ));
+
+                tokio::time::sleep(delay).await;
+            }
+            result => return result,
+        }
     }
 }
 
-fn create_video_file_name(video: &crate::extractor::Video) -> String {
-    video.title.to_string().replace("/", "_") + "_" + &video.video_type.to_string() + ".mp4"
+/// The outcome of a single (possibly resumed) download
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Downloaded {
+    /// The size of the complete file
+    pub size: u64,
+    /// The number of bytes transferred in this run
+    pub transferred: u64,
+    /// The time the transfer took
+    pub elapsed: Duration,
+}
+
+/// Downloads a single video to a (`.part`) file, resuming a partial download if the file already exists
+async fn download_video(
+    session: &Session,
+    video: &Video,
+    file_path: &Path,
+    options: &DownloadOptions,
+    observer: &dyn DownloadObserver,
+) -> Result<Downloaded> {
+    // The number of bytes downloaded in a previous run
+    let existing = fs::metadata(file_path).map(|m| m.len()).unwrap_or(0);
+
+    let client = session.client();
+    let mut request = client.get(&video.url);
+
+    if existing > 0 {
+        request = request.header(RANGE, format!("bytes={existing}-"));
+    }
+
+    // No retries here, as download_resumed already retries (and resumes) the whole download
+    let mut response = request.send().await?;
+
+    // An expired session yields the login page instead of the video
+    session.check_access(&response).await?;
+
+    if options.verbosity >= 1 {
+        observer.message(&format!("Downloading URL: {}", &video.url));
+    }
+
+    if options.verbosity >= 2 {
+        observer.message(&format!("Status code: {:#?}", &response.status()));
+        observer.message(&format!("Headers:\n{:#?}", &response.headers()));
+    }
+
+    let content_range = response
+        .headers()
+        .get(CONTENT_RANGE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(parse_content_range);
+
+    // Only append if the server honoured the range request; otherwise start from scratch
+    let offset = match (response.status(), content_range) {
+        (StatusCode::PARTIAL_CONTENT, Some(ContentRange { start, .. })) if start == existing => {
+            existing
+        }
+        (StatusCode::RANGE_NOT_SATISFIABLE, Some(ContentRange { total, .. }))
+            if existing > 0 && total == Some(existing) =>
+        {
+            // The file was already downloaded completely
+            return Ok(Downloaded {
+                size: existing,
+                transferred: 0,
+                elapsed: Duration::ZERO,
+            });
+        }
+        (StatusCode::PARTIAL_CONTENT | StatusCode::RANGE_NOT_SATISFIABLE, _) => {
+            if existing > 0 {
+                observer.message(&format!(
+                    "  Could not resume {}, restarting the download",
+                    video.title
+                ));
+            }
+
+            // Request the whole file again
+            response = client.get(&video.url).send().await?;
+            session.check_access(&response).await?;
+            response = error_for_status(response)?;
+            0
+        }
+        _ => {
+            response = error_for_status(response)?;
+            0
+        }
+    };
+
+    if offset > 0 && options.verbosity >= 1 {
+        observer.message(&format!("Resuming at {} bytes: {}", offset, video.title));
+    }
+
+    let file = fs::OpenOptions::new()
+        .create(true)
+        .write(true)
+        .append(offset > 0)
+        .truncate(offset == 0)
+        .open(file_path)?;
+
+    let mut writer = io::BufWriter::new(file);
+
+    let content_length = response.content_length();
+
+    let started = Instant::now();
+    observer.transfer_started(video, offset, content_length.map(|length| offset + length));
+
+    let mut size = offset;
+    let transfer = async {
+        while let Some(chunk) = response.chunk().await? {
+            writer.write_all(&chunk)?;
+            size += chunk.len() as u64;
+            observer.transfer_progress(video, chunk.len() as u64);
+        }
+
+        writer.flush()?;
+        Ok::<_, anyhow::Error>(())
+    }
+    .await;
+
+    observer.transfer_finished(video);
+    transfer?;
+
+    let elapsed = started.elapsed();
+
+    // Don't treat a connection which was closed early as a complete download
+    if let Some(content_length) = content_length {
+        if size != offset + content_length {
+            return Err(Error::Incomplete {
+                title: video.title.to_owned(),
+                received: size,
+                expected: offset + content_length,
+            }
+            .into());
+        }
+    }
+
+    Ok(Downloaded {
+        size,
+        transferred: size - offset,
+        elapsed,
+    })
+}
+
+/// Fails for an error status, along with the delay the server asked for before retrying (if any)
+fn error_for_status(response: Response) -> Result<Response> {
+    let status = response.status();
+    let retry_after = retry::retry_after(&response);
+
+    response
+        .error_for_status()
+        .map_err(|err| match retry_after {
+            Some(delay) => anyhow::Error::from(err).context(RetryAfter { status, delay }),
+            None => err.into(),
+        })
+}
+
+/// A parsed `Content-Range` header (e.g. `bytes 100-199/200` or `bytes */200`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct ContentRange {
+    /// The first byte of the range (zero for unsatisfied ranges)
+    start: u64,
+    /// The size of the complete file, if known
+    total: Option<u64>,
+}
+
+fn parse_content_range(value: &str) -> Option<ContentRange> {
+    let (range, total) = value.trim().strip_prefix("bytes ")?.split_once('/')?;
+
+    let total = match total {
+        "*" => None,
+        total => Some(total.parse().ok()?),
+    };
+
+    let start = match range {
+        "*" => 0,
+        range => range.split_once('-')?.0.parse().ok()?,
+    };
+
+    Some(ContentRange { start, total })
+}
+
+/// The path of the temporary file a video is downloaded to before being renamed to `file_path`
+fn part_path(file_path: &Path) -> PathBuf {
+    let mut part_path = file_path.as_os_str().to_owned();
+    part_path.push(".part");
+    PathBuf::from(part_path)
+}
+
+/// Removes `.part` files left behind by previous runs which won't be resumed.
+///
+/// These are the ones whose download was completed, and (unless only parts of the course
+/// are downloaded) the ones which don't belong to any of the videos anymore.
+fn remove_stale_part_files(
+    folder_path: &Path,
+    part_paths: &HashSet<PathBuf>,
+    complete_course: bool,
+    observer: &dyn DownloadObserver,
+) -> Result<()> {
+    for entry in fs::read_dir(folder_path)? {
+        let path = entry?.path();
+
+        if path.extension().and_then(|extension| extension.to_str()) != Some("part") {
+            continue;
+        }
+
+        let completed = path.with_extension("").exists();
+        let unknown = complete_course && !part_paths.contains(&path);
+
+        if completed || unknown {
+            observer.message(&format!("  Removing stale file {}", path.display()));
+            fs::remove_file(&path)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// The path a video is downloaded to (below the destination)
+pub fn video_path(destination: &Path, video: &Video) -> PathBuf {
+    destination.join(&video.path)
 }
 
 impl Display for TrackType {
@@ -124,3 +605,48 @@ impl Display for TrackType {
         f.write_str(lower)
     }
 }
+
+impl FromStr for TrackType {
+    type Err = Error;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Error> {
+        match s {
+            "presenter" => Ok(TrackType::Presenter),
+            "presentation" => Ok(TrackType::Presentation),
+            "raw" => Ok(TrackType::Raw),
+            "presenter_no_audio" => Ok(TrackType::PresenterNoAudio),
+            _ => Err(Error::Input(format!("Unknown track type: {}", s))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_content_range() {
+        assert_eq!(
+            parse_content_range("bytes 100-199/200"),
+            Some(ContentRange {
+                start: 100,
+                total: Some(200)
+            })
+        );
+        assert_eq!(
+            parse_content_range("bytes */200"),
+            Some(ContentRange {
+                start: 0,
+                total: Some(200)
+            })
+        );
+        assert_eq!(
+            parse_content_range("bytes 0-99/*"),
+            Some(ContentRange {
+                start: 0,
+                total: None
+            })
+        );
+        assert_eq!(parse_content_range("items 0-99/200"), None);
+    }
+}
@@ -0,0 +1,190 @@
+//! The kinds of errors, and the exit codes of the command line interface for them.
+//!
+//! | Code | Meaning                                                        |
+//! |------|----------------------------------------------------------------|
+//! | 0    | Success                                                        |
+//! | 1    | Any other error (e.g. files failing the `--verify` check)      |
+//! | 2    | Invalid options or arguments                                   |
+//! | 3    | The login token has expired, or the username/password is wrong |
+//! | 4    | Unreachable server, error response or incomplete download      |
+//! | 5    | The API answered with something we don't understand            |
+//! | 6    | Reading or writing a file failed                               |
+//! | 7    | No episodes were found, or an episode or track is missing      |
+//!
+//! Most functions return [`anyhow::Error`]s, which carry an [`Error`](enum@Error) (or the
+//! underlying [`reqwest::Error`] or [`std::io::Error`]) as their cause; [`exit_code`] finds it.
+
+use reqwest::StatusCode;
+use thiserror::Error;
+
+use std::io;
+use std::path::PathBuf;
+
+/// The exit code of any other error
+pub const EXIT_FAILURE: u8 = 1;
+/// The exit code of [`Error::Input`]
+pub const EXIT_INPUT: u8 = 2;
+/// The exit code of [`Error::AuthExpired`] and [`Error::LoginFailed`]
+pub const EXIT_AUTH: u8 = 3;
+/// The exit code of [`Error::Network`] and [`Error::Incomplete`]
+pub const EXIT_NETWORK: u8 = 4;
+/// The exit code of [`Error::Schema`]
+pub const EXIT_SCHEMA: u8 = 5;
+/// The exit code of [`Error::Filesystem`]
+pub const EXIT_FILESYSTEM: u8 = 6;
+/// The exit code of [`Error::NotFound`]
+pub const EXIT_NOT_FOUND: u8 = 7;
+
+/// The kinds of errors, each with an exit code of its own
+#[derive(Debug, Error)]
+pub enum Error {
+    /// The server rejected the login token (or it has expired)
+    #[error(
+        "Your login token is invalid or has expired. Please provide a recent JSESSIONID cookie."
+    )]
+    AuthExpired,
+    /// The server didn't accept the username and password
+    #[error("Could not log in as {0}. Please check your username and password.")]
+    LoginFailed(String),
+    /// The server could not be reached, or answered with an error status
+    #[error(transparent)]
+    Network(#[from] reqwest::Error),
+    /// The server closed the connection before sending the whole file
+    #[error("Incomplete download of {title}: got {received} of {expected} bytes")]
+    Incomplete {
+        /// The title of the video
+        title: String,
+        /// The number of bytes written
+        received: u64,
+        /// The size the server announced
+        expected: u64,
+    },
+    /// The API answered with something we can't parse (e.g. since its schema changed)
+    #[error("Couldn't parse the response of the API (at {path}): {message}")]
+    Schema {
+        /// Where in the response parsing failed
+        path: String,
+        /// What went wrong there
+        message: String,
+    },
+    /// Reading or writing a file failed
+    #[error("Could not {action} {}: {source}", .path.display())]
+    Filesystem {
+        /// What we tried to do (e.g. "write the playlist")
+        action: &'static str,
+        /// The file or folder concerned
+        path: PathBuf,
+        /// The cause
+        #[source]
+        source: io::Error,
+    },
+    /// An invalid option or argument
+    #[error("{0}")]
+    Input(String),
+    /// The server has no episodes for the target, or lacks an episode or track we rely on
+    #[error("{0}")]
+    NotFound(String),
+}
+
+impl Error {
+    /// Shorthand for an error about a file (e.g. `Error::filesystem("write", path, err)`)
+    pub fn filesystem(action: &'static str, path: impl Into<PathBuf>, source: io::Error) -> Self {
+        Error::Filesystem {
+            action,
+            path: path.into(),
+            source,
+        }
+    }
+
+    /// The exit code of the command line interface for this error
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            Error::AuthExpired | Error::LoginFailed(_) => EXIT_AUTH,
+            Error::Network(err) => network_exit_code(err),
+            Error::Incomplete { .. } => EXIT_NETWORK,
+            Error::Schema { .. } => EXIT_SCHEMA,
+            Error::Filesystem { .. } => EXIT_FILESYSTEM,
+            Error::Input(_) => EXIT_INPUT,
+            Error::NotFound(_) => EXIT_NOT_FOUND,
+        }
+    }
+}
+
+/// A rejected request is an expired login rather than a network problem
+fn network_exit_code(err: &reqwest::Error) -> u8 {
+    match err.status() {
+        Some(StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN) => EXIT_AUTH,
+        _ => EXIT_NETWORK,
+    }
+}
+
+/// The exit code for an error, going by the first known error in its chain of causes
+pub fn exit_code(err: &anyhow::Error) -> u8 {
+    err.chain()
+        .find_map(|cause| {
+            if let Some(err) = cause.downcast_ref::<Error>() {
+                Some(err.exit_code())
+            } else if let Some(err) = cause.downcast_ref::<reqwest::Error>() {
+                Some(network_exit_code(err))
+            } else if cause.is::<io::Error>() {
+                Some(EXIT_FILESYSTEM)
+            } else {
+                None
+            }
+        })
+        .unwrap_or(EXIT_FAILURE)
+}
+
+/// Sums up several errors in one, which keeps their exit code if all of them share it
+pub fn combine(message: String, mut errors: Vec<anyhow::Error>) -> anyhow::Error {
+    let codes = errors.iter().map(exit_code).collect::<Vec<_>>();
+
+    if !errors.is_empty() && codes.iter().all(|&code| code == codes[0]) {
+        errors.swap_remove(0).context(message)
+    } else {
+        anyhow::Error::msg(message)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_exit_code() {
+        let input = || anyhow::Error::from(Error::Input("Invalid number".to_owned()));
+        let io = || anyhow::Error::from(io::Error::from(io::ErrorKind::NotFound));
+
+        assert_eq!(exit_code(&input()), EXIT_INPUT);
+        assert_eq!(exit_code(&io().context("Could not read")), EXIT_FILESYSTEM);
+        assert_eq!(exit_code(&Error::AuthExpired.into()), EXIT_AUTH);
+        assert_eq!(
+            exit_code(&Error::LoginFailed("alice".to_owned()).into()),
+            EXIT_AUTH
+        );
+        assert_eq!(
+            exit_code(&Error::NotFound("No episodes found".to_owned()).into()),
+            EXIT_NOT_FOUND
+        );
+        assert_eq!(
+            exit_code(
+                &Error::Incomplete {
+                    title: "Lecture 1".to_owned(),
+                    received: 1,
+                    expected: 2
+                }
+                .into()
+            ),
+            EXIT_NETWORK
+        );
+        assert_eq!(exit_code(&anyhow::anyhow!("Oops")), EXIT_FAILURE);
+
+        let combined = combine("2 failed".to_owned(), vec![io(), io()]);
+        assert_eq!(combined.to_string(), "2 failed");
+        assert_eq!(exit_code(&combined), EXIT_FILESYSTEM);
+        assert_eq!(
+            exit_code(&combine("2 failed".to_owned(), vec![io(), input()])),
+            EXIT_FAILURE
+        );
+    }
+}
@@ -1,101 +1,536 @@
-use anyhow::{anyhow, Result};
-use reqwest::{cookie::Jar, Client, Url};
-use serde::Serialize;
+//! Queries the Opencast search API and extracts the videos to download from its responses.
+
+use anyhow::Result;
+use log::{info, warn};
+use reqwest::{StatusCode, Url};
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::str::FromStr;
 use uuid::Uuid;
 
 use crate::{
     constants,
+    error::Error,
+    retry::{self, RetryPolicy},
+    session::Session,
+    target::Target,
     types::{
-        episodes::{EpisodesData, TrackType},
-        oof,
+        episodes::{Checksum, EpisodesData, Track, TrackType},
+        series::SeriesData,
     },
 };
 
+/// Fetches all episodes of a series, paging through the search API (`page_size` episodes at a
+/// time, usually [`constants::PAGE_SIZE`]).
+///
+/// For an episode, all episodes of its series are fetched, so it is numbered and named the same
+/// as when crawling the whole series (see [`select_episodes`] to pick it again).
 pub async fn get_episodes(
-    client: &Client,
+    session: &Session,
+    server: &Url,
+    target: Target,
+    page_size: u64,
+    retry_policy: RetryPolicy,
+    verbosity: u64,
+) -> Result<EpisodesData> {
+    info!("Fetch JSON from the API...");
+
+    let target = match target {
+        Target::Episode(_) => {
+            let data =
+                get_episodes_page(session, server, 0, 1, target, retry_policy, verbosity).await?;
+
+            // Episodes without a series (or with an id we can't search for) stand on their own
+            match data
+                .search_results
+                .result
+                .first()
+                .and_then(|episode| episode.mediapackage.series.parse::<Uuid>().ok())
+            {
+                Some(series) => Target::Series(series),
+                None => return Ok(data),
+            }
+        }
+        Target::Series(_) => target,
+    };
+
+    let mut data = get_episodes_page(
+        session,
+        server,
+        0,
+        page_size,
+        target,
+        retry_policy,
+        verbosity,
+    )
+    .await?;
+    let total = data.search_results.total.max(0) as u64;
+
+    loop {
+        let fetched = data.search_results.result.len() as u64;
+
+        if fetched >= total {
+            break;
+        }
+
+        let page = get_episodes_page(
+            session,
+            server,
+            fetched,
+            page_size,
+            target,
+            retry_policy,
+            verbosity,
+        )
+        .await?;
+
+        // Don't loop forever if the server stops returning results early
+        if page.search_results.result.is_empty() {
+            break;
+        }
+
+        data.search_results
+            .result
+            .extend(page.search_results.result);
+    }
+
+    if verbosity > 0 {
+        info!(
+            "Fetched {} of {} episode(s)",
+            data.search_results.result.len(),
+            total
+        );
+    }
+
+    data.search_results.limit = data.search_results.result.len() as i64;
+
+    Ok(data)
+}
+
+/// Fetches a single page of episodes from the search API
+async fn get_episodes_page(
+    session: &Session,
+    server: &Url,
     offset: u64,
     limit: u64,
-    uuid: Uuid,
+    target: Target,
+    retry_policy: RetryPolicy,
     verbosity: u64,
 ) -> Result<EpisodesData> {
-    println!("Fetch JSON from the API...");
-
     let url = format!(
-        "https://tube.tugraz.at/search/episode.json?limit={limit}&offset={offset}&sid={uuid}",
-        uuid = uuid.to_string()
+        "{server}search/episode.json?limit={limit}&offset={offset}&{query}",
+        query = target.query()
     );
 
+    get_json(session, url, retry_policy, verbosity).await
+}
+
+/// Gets the number of episodes of a series
+pub async fn get_episode_count(
+    session: &Session,
+    server: &Url,
+    uuid: Uuid,
+    retry_policy: RetryPolicy,
+    verbosity: u64,
+) -> Result<i64> {
+    let page = get_episodes_page(
+        session,
+        server,
+        0,
+        1,
+        Target::Series(uuid),
+        retry_policy,
+        verbosity,
+    )
+    .await?;
+    Ok(page.search_results.total)
+}
+
+/// Searches for series, paging through the series search API.
+///
+/// The `query` is passed on to the server, which matches it against the metadata of the series.
+pub async fn get_series(
+    session: &Session,
+    server: &Url,
+    query: Option<&str>,
+    retry_policy: RetryPolicy,
+    verbosity: u64,
+) -> Result<SeriesData> {
+    info!("Fetch JSON from the API...");
+
+    let page_url = |offset: usize| -> Result<String> {
+        let mut url = server.join("search/series.json")?;
+        url.query_pairs_mut()
+            .append_pair("limit", &constants::PAGE_SIZE.to_string())
+            .append_pair("offset", &offset.to_string());
+
+        if let Some(query) = query {
+            url.query_pairs_mut().append_pair("q", query);
+        }
+
+        Ok(url.to_string())
+    };
+
+    let mut data: SeriesData = get_json(session, page_url(0)?, retry_policy, verbosity).await?;
+
+    while (data.search_results.result.len() as i64) < data.search_results.total {
+        let offset = data.search_results.result.len();
+        let page: SeriesData =
+            get_json(session, page_url(offset)?, retry_policy, verbosity).await?;
+
+        // Don't loop forever if the server stops returning results early
+        if page.search_results.result.is_empty() {
+            break;
+        }
+
+        data.search_results
+            .result
+            .extend(page.search_results.result);
+    }
+
+    Ok(data)
+}
+
+/// A page of results of the search API
+trait SearchPage: DeserializeOwned {
+    /// Whether the results were left out although there are some, as they are for anonymous users.
+    ///
+    /// Only the first page tells, as later ones may come up empty if episodes were removed
+    /// in the meantime (which the paging stops at).
+    fn is_withheld(&self) -> bool;
+}
+
+impl SearchPage for EpisodesData {
+    fn is_withheld(&self) -> bool {
+        let results = &self.search_results;
+        results.offset == 0 && results.result.is_empty() && results.total > 0
+    }
+}
+
+impl SearchPage for SeriesData {
+    fn is_withheld(&self) -> bool {
+        let results = &self.search_results;
+        results.offset == 0 && results.result.is_empty() && results.total > 0
+    }
+}
+
+/// Fetches and parses JSON from the API, renewing the session if it expired
+async fn get_json<T: SearchPage>(
+    session: &Session,
+    url: String,
+    retry_policy: RetryPolicy,
+    verbosity: u64,
+) -> Result<T> {
     if verbosity > 0 {
-        println!("Using URL: {}", &url);
+        info!("Using URL: {}", &url);
     }
 
-    let text = client.get(url).send().await?.text().await?;
+    session
+        .with_renewal(|| fetch_json(session, &url, retry_policy, verbosity))
+        .await
+}
+
+async fn fetch_json<T: SearchPage>(
+    session: &Session,
+    url: &str,
+    retry_policy: RetryPolicy,
+    verbosity: u64,
+) -> Result<T> {
+    let response = retry::send(retry_policy, session.client().get(url), |message| {
+        warn!("{}", message)
+    })
+    .await?;
+    session.check_access(&response).await?;
+
+    // Unknown series and episodes aren't worth parsing the error page of
+    if response.status() == StatusCode::NOT_FOUND {
+        return Err(Error::NotFound(format!("Nothing found at {}", url)).into());
+    }
+    let text = response.error_for_status()?.text().await?;
 
     let parsed = serde_path_to_error::deserialize(&mut serde_json::Deserializer::from_str(&text));
-    parsed.map_err(|e| {
+    let parsed: T = parsed.map_err(|e| {
         if verbosity >= 4 {
-            println!("---Begin of full text dump---\n{text}\n---End of full text dump---");
+            info!("---Begin of full text dump---\n{text}\n---End of full text dump---");
         }
 
         if verbosity >= 1 {
-            println!("Full error:\n{}", e.path());
+            info!("Full error:\n{}", e.path());
         }
 
-        if serde_json::from_str::<oof::Root>(&text).is_ok() {
-            anyhow!("Is your login token (still) valid? Please provide a recent JSESSIONID cookie.")
-        } else {
-            anyhow!("Couldn't parse JSON from the API. Unknown error: {}", e)
+        Error::Schema {
+            path: e.path().to_string(),
+            message: e.inner().to_string(),
         }
-    })
-}
+    })?;
 
-pub fn make_client(token: &str) -> Result<Client> {
-    let url = Url::parse(constants::BASE_URL)?;
-
-    let jar = Jar::default();
-    jar.add_cookie_str(&format!("JSESSIONID={}", token), &url);
+    // The API leaves out the results if we aren't logged in
+    if parsed.is_withheld() {
+        return Err(Error::AuthExpired.into());
+    }
 
-    Ok(Client::builder().cookie_provider(jar.into()).build()?)
+    Ok(parsed)
 }
 
-pub fn extract_course_data(data: &EpisodesData) -> Result<Course> {
-    println!("Extracting data...");
+/// Picks the tracks to download from the episodes, numbering the episodes in the order they were
+/// recorded. Fails if there are no episodes at all.
+pub fn extract_course_data(data: &EpisodesData, selection: &TrackSelection) -> Result<Course> {
+    info!("Extracting data...");
 
-    let first = data.search_results.result.get(0).unwrap();
+    let first = data.search_results.result.first().ok_or_else(|| {
+        Error::NotFound(
+            concat!(
+                "No episodes found. ",
+                "If there should be some, please provide a recent JSESSIONID cookie."
+            )
+            .to_owned(),
+        )
+    })?;
 
     // TODO implement progress bar or remove it
     // let pb = ProgressBar::new(data.search_results.result.len().try_into().unwrap());
 
+    // Number the episodes in the order they were recorded
+    let mut episodes = data.search_results.result.iter().collect::<Vec<_>>();
+    episodes.sort_by(|a, b| a.mediapackage.start.cmp(&b.mediapackage.start));
+    let indices = episodes
+        .iter()
+        .enumerate()
+        .map(|(i, result)| (result.id.as_str(), i + 1))
+        .collect::<HashMap<_, _>>();
+
     let mut videos = Vec::new();
 
     for result in &data.search_results.result {
-        let tracks = result.mediapackage.media.track.iter().filter(|track| {
-            track.mimetype == constants::MP4_MIME
-                && track
-                    .tags
-                    .tag
-                    .contains(&constants::HIGH_QUALITY.to_string())
-        });
-
-        for track in tracks {
+        let tracks = select_tracks(&result.mediapackage.media.track, selection);
+
+        if tracks.is_empty() {
+            warn!("  No matching tracks for {}", result.mediapackage.title);
+        }
+
+        for (track, quality) in tracks {
+            if let (Some(wanted), Some(quality)) = (selection.quality.tag(), quality) {
+                if wanted != quality {
+                    warn!(
+                        "  Using {} instead of {} quality for the {} of {}",
+                        quality, wanted, track.type_field, result.mediapackage.title
+                    );
+                }
+            }
+
             videos.push(Video {
                 url: track.url.to_owned(), // TODO change this back to a borrow
                 title: result.mediapackage.title.to_owned(), // TODO change this back to a borrow
                 id: result.id.to_owned(),  // TODO change this back to a borrow
+                track_id: track.id.to_owned(),
                 video_type: track.type_field,
+                quality: quality.map(str::to_owned),
+                checksum: track.checksum.to_owned(),
+                start: result.mediapackage.start.to_owned(),
+                duration: track.duration,
+                mimetype: track.mimetype.to_owned(),
+                index: indices[result.id.as_str()],
+                presenters: result
+                    .dc_creator
+                    .iter()
+                    .filter_map(Value::as_str)
+                    .map(str::to_owned)
+                    .collect(),
+                path: PathBuf::new(),
             });
         }
     }
 
-    let course = Course {
-        title: first.mediapackage.seriestitle.to_owned(), // TODO change this back to a borrow
-        id: first.mediapackage.series.to_owned(),         // TODO change this back to a borrow
-        videos,
+    let partial = (data.search_results.result.len() as i64) < data.search_results.total;
+
+    // Episodes without a series get a course of their own
+    let course = if first.mediapackage.series.is_empty() {
+        Course {
+            title: first.mediapackage.title.to_owned(),
+            id: first.id.to_owned(),
+            videos,
+            partial,
+        }
+    } else {
+        Course {
+            title: first.mediapackage.seriestitle.to_owned(), // TODO change this back to a borrow
+            id: first.mediapackage.series.to_owned(),         // TODO change this back to a borrow
+            videos,
+            partial,
+        }
     };
 
     Ok(course)
 }
 
+/// Keeps the videos of the episodes to download: the episode of an episode target, and (of those)
+/// the ones left after skipping the first `skip` episodes in the order they were recorded and
+/// keeping at most `limit` of the rest.
+///
+/// Indices and paths aren't touched, so call it after [`template::assign_paths`] to name the
+/// videos the same as when crawling the whole series.
+///
+/// [`template::assign_paths`]: crate::template::assign_paths
+pub fn select_episodes(course: &mut Course, target: Target, skip: u64, limit: Option<u64>) {
+    let mut indices = course
+        .videos
+        .iter()
+        .filter(|video| match target {
+            Target::Episode(uuid) => video.id.eq_ignore_ascii_case(&uuid.to_string()),
+            Target::Series(_) => true,
+        })
+        .map(|video| video.index)
+        .collect::<Vec<_>>();
+    indices.sort_unstable();
+    indices.dedup();
+
+    let kept = indices
+        .into_iter()
+        .skip(skip as usize)
+        .take(limit.map_or(usize::MAX, |limit| limit as usize))
+        .collect::<HashSet<_>>();
+
+    let count = course.videos.len();
+    course.videos.retain(|video| kept.contains(&video.index));
+
+    // A single episode shares the folder with the rest of its series (even if we couldn't fetch it)
+    course.partial |= course.videos.len() < count || matches!(target, Target::Episode(_));
+}
+
+/// Selects (at most) one track per track type, along with its quality tag.
+///
+/// If the requested quality isn't available, the next-lower quality is used instead,
+/// falling back to higher qualities if there is no lower one.
+fn select_tracks<'a>(
+    tracks: &'a [Track],
+    selection: &TrackSelection,
+) -> Vec<(&'a Track, Option<&'static str>)> {
+    let candidates = tracks.iter().filter(|track| {
+        track.mimetype == selection.mimetype
+            && selection
+                .track_types
+                .as_ref()
+                .map_or(true, |types| types.contains(&track.type_field))
+    });
+
+    let mut selected: Vec<(&Track, Option<&'static str>)> = Vec::new();
+
+    for track in candidates {
+        let quality = track_quality(track);
+
+        // The position of the track's quality in our preferences (lower is better)
+        let rank = |quality: Option<&str>| {
+            selection
+                .quality
+                .preferences()
+                .iter()
+                .position(|&preferred| Some(preferred) == quality)
+        };
+
+        // Tracks without a (known) quality tag are only used for `best` and `smallest`
+        if rank(quality).is_none() && selection.quality.tag().is_some() {
+            continue;
+        }
+
+        match selected
+            .iter_mut()
+            .find(|(other, _)| other.type_field == track.type_field)
+        {
+            Some(entry) => {
+                let better = match (rank(quality), rank(entry.1)) {
+                    (Some(new), Some(old)) => new < old,
+                    (Some(_), None) => true,
+                    _ => false,
+                };
+
+                if better {
+                    *entry = (track, quality);
+                }
+            }
+            None => selected.push((track, quality)),
+        }
+    }
+
+    selected
+}
+
+/// Finds the quality tag of a track (e.g. `high` or `high-quality`)
+fn track_quality(track: &Track) -> Option<&'static str> {
+    constants::QUALITIES.iter().copied().find(|&quality| {
+        track
+            .tags
+            .tag
+            .iter()
+            .any(|tag| tag == quality || *tag == format!("{quality}-quality"))
+    })
+}
+
+/// The video quality to download
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Quality {
+    /// The high quality, or the closest one available
+    High,
+    /// The medium quality, or the closest one available
+    Medium,
+    /// The low quality, or the closest one available
+    Low,
+    /// The highest quality available
+    Best,
+    /// The lowest quality available
+    Smallest,
+}
+
+impl Quality {
+    /// The requested quality tag (`None` for `best` and `smallest`)
+    fn tag(self) -> Option<&'static str> {
+        match self {
+            Quality::High => Some(constants::HIGH_QUALITY),
+            Quality::Medium => Some(constants::MEDIUM_QUALITY),
+            Quality::Low => Some(constants::LOW_QUALITY),
+            Quality::Best | Quality::Smallest => None,
+        }
+    }
+
+    /// The quality tags in order of preference
+    fn preferences(self) -> [&'static str; 3] {
+        use constants::{HIGH_QUALITY as HIGH, LOW_QUALITY as LOW, MEDIUM_QUALITY as MEDIUM};
+
+        match self {
+            Quality::High | Quality::Best => [HIGH, MEDIUM, LOW],
+            Quality::Medium => [MEDIUM, LOW, HIGH],
+            Quality::Low | Quality::Smallest => [LOW, MEDIUM, HIGH],
+        }
+    }
+}
+
+impl FromStr for Quality {
+    type Err = Error;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Error> {
+        match s {
+            "high" => Ok(Quality::High),
+            "medium" => Ok(Quality::Medium),
+            "low" => Ok(Quality::Low),
+            "best" => Ok(Quality::Best),
+            "smallest" => Ok(Quality::Smallest),
+            _ => Err(Error::Input(format!("Unknown quality: {}", s))),
+        }
+    }
+}
+
+/// Which tracks of an episode to download
+#[derive(Debug, Clone)]
+pub struct TrackSelection {
+    /// The quality to pick among the tracks of the same type
+    pub quality: Quality,
+    /// The track types to keep (`None` keeps all of them)
+    pub track_types: Option<Vec<TrackType>>,
+    /// The mimetype of the tracks to keep (e.g. `video/mp4`)
+    pub mimetype: String,
+}
+
 // #[derive(Debug, Serialize, Clone)]
 // pub struct Video<'a> {
 //     pub url: &'a str,
@@ -111,32 +546,135 @@ pub fn extract_course_data(data: &EpisodesData) -> Result<Course> {
 //     pub videos: Vec<Video<'a>>,
 // }
 
+/// A track of an episode to download
 #[derive(Debug, Serialize, Clone)]
 pub struct Video {
+    /// Where the track is downloaded from
     pub url: String,
+    /// The title of the episode
     pub title: String,
+    /// The id of the episode
     pub id: String,
+    /// The id of the track, which tells the tracks of an episode apart
+    pub track_id: String,
+    /// Which view of the lecture the track shows (e.g. the presenter or the slides)
     pub video_type: TrackType,
+    /// The quality tag of the track (if it has one)
+    pub quality: Option<String>,
+    /// The checksum advertised by the API (if any)
+    pub checksum: Option<Checksum>,
+    /// When the lecture was recorded
+    pub start: String,
+    /// The duration in milliseconds
+    pub duration: i64,
+    /// The mimetype of the track (e.g. `video/mp4`)
+    pub mimetype: String,
+    /// The chronological position of the episode in its series (starting at 1)
+    pub index: usize,
+    /// The lecturers of the episode
+    pub presenters: Vec<String>,
+    /// The path below the destination (see `template::assign_paths`)
+    #[serde(skip)]
+    pub path: PathBuf,
 }
 
+/// A series (or the part of it that was crawled) with the tracks to download
 #[derive(Debug, Serialize, Clone)]
 pub struct Course {
+    /// The title of the series
     pub title: String,
+    /// The id of the series
     pub id: String,
+    /// The tracks of all episodes
     pub videos: Vec<Video>,
+    /// Whether only some of the episodes of the course were crawled (or kept)
+    #[serde(skip)]
+    pub partial: bool,
 }
 
+#[cfg(test)]
 mod test {
+    #[test]
+    fn test_select_tracks() {
+        use super::*;
+        use crate::types::episodes::Tags;
+
+        let track = |id: &str, type_field, tag: &str| Track {
+            id: id.to_owned(),
+            type_field,
+            mimetype: constants::MP4_MIME.to_owned(),
+            tags: Tags {
+                tag: vec![tag.to_owned()],
+            },
+            ..Default::default()
+        };
+
+        let tracks = [
+            track("presenter-low", TrackType::Presenter, "low"),
+            track("presenter-high", TrackType::Presenter, "high-quality"),
+            track("presentation-low", TrackType::Presentation, "low"),
+            track("presentation-medium", TrackType::Presentation, "medium"),
+        ];
+
+        let select = |quality, track_types| {
+            let selection = TrackSelection {
+                quality,
+                track_types,
+                mimetype: constants::MP4_MIME.to_owned(),
+            };
+
+            select_tracks(&tracks, &selection)
+                .into_iter()
+                .map(|(track, _)| track.id.as_str())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            select(Quality::High, None),
+            ["presenter-high", "presentation-medium"]
+        );
+        assert_eq!(
+            select(Quality::Smallest, None),
+            ["presenter-low", "presentation-low"]
+        );
+        assert_eq!(
+            select(Quality::Medium, Some(vec![TrackType::Presenter])),
+            ["presenter-low"]
+        );
+    }
+
     #[test]
     fn test_get_episodes() {
         use super::*;
-        use std::fs;
-        let text = fs::read_to_string("private/episodes_test.json").unwrap();
 
-        let parsed: anyhow::Result<EpisodesData> =
+        let text = include_str!("../tests/fixtures/episodes_multi.json")
+            .replace("{{server}}", "https://example.org/");
+
+        let data: EpisodesData =
             serde_path_to_error::deserialize(&mut serde_json::Deserializer::from_str(&text))
-                .map_err(anyhow::Error::from);
+                .unwrap();
+        assert_eq!(data.search_results.total, 3);
+
+        let selection = TrackSelection {
+            quality: Quality::High,
+            track_types: Some(vec![TrackType::Presenter]),
+            mimetype: constants::MP4_MIME.to_owned(),
+        };
+        let course = extract_course_data(&data, &selection).unwrap();
 
-        let data = parsed.unwrap();
+        assert_eq!(course.title, "Example Analysis");
+        assert!(!course.partial);
+        assert_eq!(
+            course
+                .videos
+                .iter()
+                .map(|video| (video.index, video.track_id.as_str()))
+                .collect::<Vec<_>>(),
+            [
+                (2, "t-b2-presenter-high"),
+                (1, "t-b1-presenter-high"),
+                (3, "t-b3-presenter-high")
+            ]
+        );
     }
 }
@@ -0,0 +1,296 @@
+//! Writes a series as an RSS 2.0 podcast feed.
+
+use anyhow::Result;
+use chrono::DateTime;
+use html_escape::{encode_double_quoted_attribute as encode_attribute, encode_text};
+use reqwest::Url;
+
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use crate::constants;
+use crate::error::Error;
+use crate::extractor::{self, Course, TrackSelection};
+use crate::retry::RetryPolicy;
+use crate::sanitize::SanitizeMode;
+use crate::session::Session;
+use crate::target::Target;
+use crate::template::{self, Template};
+use crate::types::episodes::{Attachment, EpisodesData, Result as Episode};
+
+/// The attachment types of cover images, in order of preference
+const COVER_TYPES: [&str; 2] = ["/player+preview", "/search+preview"];
+
+/// Which series to write as a feed, and how
+#[derive(Debug, Clone)]
+pub struct FeedOptions {
+    /// The base URL of the Opencast server
+    pub server: Url,
+    /// The series (or the series of the episode) to write
+    pub target: Target,
+    /// Where the downloaded files are served (enclosures point at the server if `None`)
+    pub base_url: Option<Url>,
+    /// The paths of the downloaded files below the `base_url`
+    pub output_template: Template,
+    /// How to turn titles into file names
+    pub sanitize_mode: SanitizeMode,
+    /// Which track of each episode to use as the enclosure
+    pub track_selection: TrackSelection,
+    /// How often to retry the requests to the API
+    pub retry_policy: RetryPolicy,
+    /// How many details to report (0 is the least)
+    pub verbosity: u64,
+}
+
+/// A series rendered as a podcast feed
+#[derive(Debug, Clone)]
+pub struct Feed {
+    /// The RSS document
+    pub xml: String,
+    /// The number of items in it (one per video)
+    pub items: usize,
+}
+
+/// Crawls a series and renders it as an RSS 2.0 podcast feed
+pub async fn build_feed(options: &FeedOptions, session: &Session) -> Result<Feed> {
+    let data = extractor::get_episodes(
+        session,
+        &options.server,
+        options.target,
+        constants::PAGE_SIZE,
+        options.retry_policy,
+        options.verbosity,
+    )
+    .await?;
+
+    let mut course = extractor::extract_course_data(&data, &options.track_selection)?;
+    template::assign_paths(&mut course, &options.output_template, options.sanitize_mode);
+    extractor::select_episodes(&mut course, options.target, 0, None);
+
+    Ok(Feed {
+        xml: render_feed(&data, &course, options)?,
+        items: course.videos.len(),
+    })
+}
+
+/// Renders the videos of a course as RSS items, newest first.
+///
+/// With a `base_url`, the enclosures point at the downloaded files (in the folder layout of
+/// the downloads) below it instead of at the server.
+fn render_feed(data: &EpisodesData, course: &Course, options: &FeedOptions) -> Result<String> {
+    let server = &options.server;
+
+    let episodes = data
+        .search_results
+        .result
+        .iter()
+        .map(|episode| (episode.id.as_str(), episode))
+        .collect::<HashMap<_, _>>();
+
+    let mut videos = course.videos.iter().collect::<Vec<_>>();
+    videos.sort_by(|a, b| b.start.cmp(&a.start));
+
+    let mut text = String::from(concat!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n",
+        "<rss version=\"2.0\" xmlns:itunes=\"http://www.itunes.com/dtds/podcast-1.0.dtd\">\n",
+        "  <channel>\n"
+    ));
+
+    writeln!(text, "    <title>{}</title>", encode_text(&course.title))?;
+    writeln!(text, "    <link>{}</link>", encode_text(server.as_str()))?;
+    writeln!(
+        text,
+        "    <description>Lecture recordings of {}</description>",
+        encode_text(&course.title)
+    )?;
+
+    // Series have no cover of their own, so use the one of the latest episode
+    let cover = videos
+        .iter()
+        .filter_map(|video| episodes.get(video.id.as_str()))
+        .find_map(|episode| find_cover(episode));
+
+    if let Some(cover) = cover {
+        text.push_str("    <image>\n");
+        writeln!(text, "      <url>{}</url>", encode_text(&cover.url))?;
+        writeln!(text, "      <title>{}</title>", encode_text(&course.title))?;
+        writeln!(text, "      <link>{}</link>", encode_text(server.as_str()))?;
+        text.push_str("    </image>\n");
+        writeln!(
+            text,
+            "    <itunes:image href=\"{}\"/>",
+            encode_attribute(&cover.url)
+        )?;
+    }
+
+    for video in videos {
+        let episode = episodes
+            .get(video.id.as_str())
+            .ok_or_else(|| Error::NotFound(format!("Unknown episode {}", video.id)))?;
+        let track = episode
+            .mediapackage
+            .media
+            .track
+            .iter()
+            .find(|track| track.id == video.track_id)
+            .ok_or_else(|| Error::NotFound(format!("Unknown track {}", video.track_id)))?;
+
+        // Only tell the tracks of an episode apart if there are several of them
+        let title = if course.videos.iter().filter(|v| v.id == video.id).count() > 1 {
+            format!("{} ({})", video.title, video.video_type)
+        } else {
+            video.title.to_owned()
+        };
+
+        let url = match &options.base_url {
+            Some(base_url) => {
+                let mut url = base_url.to_owned();
+                url.path_segments_mut()
+                    .map_err(|_| Error::Input(format!("Invalid base URL {}", base_url)))?
+                    .pop_if_empty()
+                    .extend(video.path.iter().map(|segment| segment.to_string_lossy()));
+                url.to_string()
+            }
+            None => video.url.to_owned(),
+        };
+
+        text.push_str("    <item>\n");
+        writeln!(text, "      <title>{}</title>", encode_text(&title))?;
+        writeln!(
+            text,
+            "      <guid isPermaLink=\"false\">{}/{}</guid>",
+            encode_text(&video.id),
+            encode_text(&video.track_id)
+        )?;
+        writeln!(
+            text,
+            "      <enclosure url=\"{}\" type=\"{}\" length=\"{}\"/>",
+            encode_attribute(&url),
+            encode_attribute(&track.mimetype),
+            track.size.unwrap_or(0)
+        )?;
+
+        if let Ok(start) = DateTime::parse_from_rfc3339(&video.start) {
+            writeln!(text, "      <pubDate>{}</pubDate>", start.to_rfc2822())?;
+        }
+
+        writeln!(
+            text,
+            "      <itunes:duration>{}</itunes:duration>",
+            format_duration(video.duration)
+        )?;
+
+        if let Some(description) = &episode.dc_description {
+            writeln!(
+                text,
+                "      <description>{}</description>",
+                encode_text(description)
+            )?;
+        }
+
+        if let Some(cover) = find_cover(episode) {
+            writeln!(
+                text,
+                "      <itunes:image href=\"{}\"/>",
+                encode_attribute(&cover.url)
+            )?;
+        }
+
+        text.push_str("    </item>\n");
+    }
+
+    text.push_str("  </channel>\n</rss>\n");
+    Ok(text)
+}
+
+/// Finds the preview image of an episode
+fn find_cover(episode: &Episode) -> Option<&Attachment> {
+    let attachments = &episode.mediapackage.attachments.attachment;
+
+    COVER_TYPES.iter().find_map(|cover_type| {
+        attachments.iter().find(|attachment| {
+            attachment.type_field.ends_with(cover_type) && attachment.mimetype.starts_with("image/")
+        })
+    })
+}
+
+/// Formats a duration (in milliseconds) as `HH:MM:SS`
+fn format_duration(duration: i64) -> String {
+    let seconds = duration.max(0) / 1000;
+    format!(
+        "{:02}:{:02}:{:02}",
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::constants;
+    use crate::extractor::Quality;
+    use crate::types::episodes::TrackType;
+    use uuid::Uuid;
+
+    #[test]
+    fn test_render_feed() {
+        let data: EpisodesData = serde_json::from_value(serde_json::json!({
+            "search-results": {
+                "offset": 0, "limit": 100, "total": 1, "searchTime": 1, "query": "",
+                "result": {
+                    "id": "episode", "org": "org", "dcDescription": "Limits & series",
+                    "mediapackage": {
+                        "duration": 5_400_000, "id": "episode", "start": "2021-10-01T08:15:00Z",
+                        "title": "Lecture 1", "series": "series", "seriestitle": "Analysis",
+                        "media": { "track": {
+                            "id": "track", "type": "presenter/delivery", "ref": "",
+                            "mimetype": "video/mp4", "tags": { "tag": "high-quality" },
+                            "url": "https://example.org/video.mp4", "duration": 5_400_000,
+                            "size": 1234
+                        } },
+                        "metadata": {},
+                        "attachments": { "attachment": {
+                            "id": "cover", "type": "presenter/player+preview",
+                            "mimetype": "image/jpeg", "tags": {},
+                            "url": "https://example.org/cover.jpg"
+                        } },
+                        "publications": ""
+                    }
+                }
+            }
+        }))
+        .unwrap();
+
+        let selection = TrackSelection {
+            quality: Quality::High,
+            track_types: Some(vec![TrackType::Presenter]),
+            mimetype: "video/mp4".to_owned(),
+        };
+        let mut course = extractor::extract_course_data(&data, &selection).unwrap();
+        let options = FeedOptions {
+            server: Url::parse("https://tube.example.org/").unwrap(),
+            target: Target::Series(Uuid::nil()),
+            base_url: Some(Url::parse("https://files.example.org/lectures/").unwrap()),
+            output_template: constants::DEFAULT_TEMPLATE.parse().unwrap(),
+            sanitize_mode: SanitizeMode::Windows,
+            track_selection: selection,
+            retry_policy: RetryPolicy { max_attempts: 1 },
+            verbosity: 0,
+        };
+
+        template::assign_paths(&mut course, &options.output_template, options.sanitize_mode);
+        let feed = render_feed(&data, &course, &options).unwrap();
+
+        assert!(feed.contains("<title>Analysis</title>"));
+        assert!(feed.contains("<title>Lecture 1</title>"));
+        assert!(feed.contains(concat!(
+            "<enclosure url=\"https://files.example.org/lectures/Analysis/Lecture%201_presenter.mp4\"",
+            " type=\"video/mp4\" length=\"1234\"/>"
+        )));
+        assert!(feed.contains("<pubDate>Fri, 01 Oct 2021 08:15:00 +0000</pubDate>"));
+        assert!(feed.contains("<itunes:duration>01:30:00</itunes:duration>"));
+        assert!(feed.contains("<description>Limits &amp; series</description>"));
+        assert!(feed.contains("<itunes:image href=\"https://example.org/cover.jpg\"/>"));
+    }
+}
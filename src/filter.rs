@@ -0,0 +1,207 @@
+//! Includes or excludes videos by their file names and paths.
+
+use std::fmt::Display;
+use std::path::Path;
+
+use regex::Regex;
+
+use crate::download;
+use crate::extractor::{Course, Video};
+
+/// The regexes deciding which videos to download
+#[derive(Debug, Clone, Default)]
+pub struct Filters {
+    /// Excludes videos whose file name matches
+    pub file_filter: Option<Regex>,
+    /// Excludes videos whose path matches
+    pub path_filter: Option<Regex>,
+    /// Excludes videos whose file name doesn't match
+    pub file_matcher: Option<Regex>,
+    /// Excludes videos whose path doesn't match
+    pub path_matcher: Option<Regex>,
+}
+
+/// The reason why a video was excluded from the download
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FilterReason {
+    /// The file name matched the `--file-filter`
+    FileFilter,
+    /// The destination path matched the `--path-filter`
+    PathFilter,
+    /// The file name didn't match the `--file-matcher`
+    FileMatcher,
+    /// The destination path didn't match the `--path-matcher`
+    PathMatcher,
+}
+
+/// Applies the file & path filters and matchers to the videos of a course.
+///
+/// The regexes are evaluated against the generated file name and the full destination path.
+/// Filters take precedence over matchers: a video is excluded if it matches any filter,
+/// even if it also matches the matchers. Otherwise, it has to match every given matcher.
+///
+/// Returns the course with the remaining videos, along with the excluded ones.
+pub fn filter_course(
+    mut course: Course,
+    filters: &Filters,
+    destination: &Path,
+) -> (Course, Vec<(Video, FilterReason)>) {
+    let mut excluded = Vec::new();
+
+    course.videos.retain(|video| {
+        let path = download::video_path(destination, video);
+        let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+
+        match check_video(filters, &file_name, &path) {
+            Some(reason) => {
+                excluded.push((video.to_owned(), reason));
+                false
+            }
+            None => true,
+        }
+    });
+
+    course.partial |= !excluded.is_empty();
+
+    (course, excluded)
+}
+
+/// Checks a single video against the filters and matchers, returning the reason for its exclusion
+fn check_video(filters: &Filters, file_name: &str, path: &Path) -> Option<FilterReason> {
+    let path = path.to_string_lossy();
+
+    let is_match = |regex: &Option<Regex>, text: &str| regex.as_ref().map(|r| r.is_match(text));
+
+    if is_match(&filters.file_filter, file_name) == Some(true) {
+        Some(FilterReason::FileFilter)
+    } else if is_match(&filters.path_filter, &path) == Some(true) {
+        Some(FilterReason::PathFilter)
+    } else if is_match(&filters.file_matcher, file_name) == Some(false) {
+        Some(FilterReason::FileMatcher)
+    } else if is_match(&filters.path_matcher, &path) == Some(false) {
+        Some(FilterReason::PathMatcher)
+    } else {
+        None
+    }
+}
+
+impl Display for FilterReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            FilterReason::FileFilter => "file name matches the file filter",
+            FilterReason::PathFilter => "path matches the path filter",
+            FilterReason::FileMatcher => "file name doesn't match the file matcher",
+            FilterReason::PathMatcher => "path doesn't match the path matcher",
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::types::episodes::TrackType;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_check_video() {
+        let regex = |pattern: &str| Some(Regex::new(pattern).unwrap());
+        let path = Path::new("/videos/Analysis/Lecture 1_presenter.mp4");
+        let check = |filters: &Filters| check_video(filters, "Lecture 1_presenter.mp4", path);
+
+        assert_eq!(check(&Filters::default()), None);
+
+        let filters = Filters {
+            file_filter: regex("presenter"),
+            ..Default::default()
+        };
+        assert_eq!(check(&filters), Some(FilterReason::FileFilter));
+
+        // The path filter sees the folders, the file filter only the file name
+        let filters = Filters {
+            file_filter: regex("Analysis"),
+            path_filter: regex("Analysis/"),
+            ..Default::default()
+        };
+        assert_eq!(check(&filters), Some(FilterReason::PathFilter));
+
+        let filters = Filters {
+            file_matcher: regex("presentation"),
+            ..Default::default()
+        };
+        assert_eq!(check(&filters), Some(FilterReason::FileMatcher));
+
+        // Every matcher has to match
+        let filters = Filters {
+            file_matcher: regex("Lecture"),
+            path_matcher: regex("Algebra"),
+            ..Default::default()
+        };
+        assert_eq!(check(&filters), Some(FilterReason::PathMatcher));
+
+        let filters = Filters {
+            file_matcher: regex("Lecture"),
+            path_matcher: regex("Analysis"),
+            ..Default::default()
+        };
+        assert_eq!(check(&filters), None);
+
+        // Filters take precedence over matchers
+        let filters = Filters {
+            file_filter: regex("Lecture 1"),
+            file_matcher: regex("Lecture"),
+            ..Default::default()
+        };
+        assert_eq!(check(&filters), Some(FilterReason::FileFilter));
+    }
+
+    #[test]
+    fn test_filter_course() {
+        let video = |title: &str, video_type| Video {
+            url: format!("https://example.org/{title}.mp4"),
+            title: title.to_owned(),
+            id: title.to_owned(),
+            track_id: format!("{title}-{video_type}"),
+            video_type,
+            quality: None,
+            checksum: None,
+            start: "2021-10-01T08:15:00Z".to_owned(),
+            duration: 5_400_000,
+            mimetype: "video/mp4".to_owned(),
+            index: 1,
+            presenters: Vec::new(),
+            path: PathBuf::from(format!("Analysis/{title}_{video_type}.mp4")),
+        };
+
+        let course = Course {
+            title: "Analysis".to_owned(),
+            id: "series".to_owned(),
+            videos: vec![
+                video("Lecture 1", TrackType::Presenter),
+                video("Lecture 1", TrackType::Presentation),
+                video("Exercise 1", TrackType::Presenter),
+            ],
+            partial: false,
+        };
+
+        let filters = Filters {
+            file_filter: Regex::new("presentation").ok(),
+            file_matcher: Regex::new("^Lecture").ok(),
+            ..Default::default()
+        };
+        let (course, excluded) = filter_course(course, &filters, Path::new("videos"));
+
+        assert!(course.partial);
+        assert_eq!(course.videos.len(), 1);
+        assert_eq!(course.videos[0].track_id, "Lecture 1-presenter");
+        assert_eq!(
+            excluded
+                .iter()
+                .map(|(video, reason)| (video.track_id.as_str(), *reason))
+                .collect::<Vec<_>>(),
+            [
+                ("Lecture 1-presentation", FilterReason::FileFilter),
+                ("Exercise 1-presenter", FilterReason::FileMatcher)
+            ]
+        );
+    }
+}
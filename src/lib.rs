@@ -0,0 +1,77 @@
+//! Crawls & downloads lecture recordings from TU Graz TUbe and other Opencast servers.
+//!
+//! The `tube-get` binary is a thin command line interface on top of this library:
+//!
+//! 1. Log in to the server with a [`session::SessionBuilder`] (with a token, or with
+//!    [`session::Credentials`]), which may renew the [`session::Session`] once it expires.
+//! 2. Fetch the episodes of a series (or a single episode) with [`extractor::get_episodes`].
+//! 3. Select the tracks to download with [`extractor::extract_course_data`], yielding a
+//!    [`extractor::Course`] of [`extractor::Video`]s, and give them paths with
+//!    [`template::assign_paths`].
+//! 4. Download them with [`download::download_course`], which reports its progress to a
+//!    [`download::DownloadObserver`].
+//!
+//! The library never prints anything: status messages and warnings are logged with the
+//! [`log`](https://docs.rs/log) crate instead, and results are returned to the caller.
+//!
+//! ```no_run
+//! use std::sync::Arc;
+//!
+//! use tube_get::download::{self, DownloadOptions, Silent};
+//! use tube_get::extractor::{self, Quality, TrackSelection};
+//! use tube_get::retry::RetryPolicy;
+//! use tube_get::sanitize::SanitizeMode;
+//! use tube_get::session::SessionBuilder;
+//! use tube_get::target::Target;
+//! use tube_get::template::{self, Template};
+//!
+//! # async fn run() -> anyhow::Result<()> {
+//! let server: reqwest::Url = "https://tube.tugraz.at/".parse()?;
+//! let session = SessionBuilder::new(server.clone()).token("JSESSIONID").build()?;
+//! let retry_policy = RetryPolicy { max_attempts: 5 };
+//!
+//! let target = "9c4dd2c5-1ba8-4e6d-98a9-5d4bb9c4c9c3".parse::<Target>()?;
+//! let data = extractor::get_episodes(&session, &server, target, 100, retry_policy, 0).await?;
+//!
+//! let selection = TrackSelection {
+//!     quality: Quality::High,
+//!     track_types: None,
+//!     mimetype: "video/mp4".to_owned(),
+//! };
+//! let mut course = extractor::extract_course_data(&data, &selection)?;
+//! let template = "{series}/{title}_{type}.{ext}".parse::<Template>()?;
+//! template::assign_paths(&mut course, &template, SanitizeMode::Posix);
+//!
+//! // Only the first three lectures
+//! extractor::select_episodes(&mut course, target, 0, Some(3));
+//!
+//! let options = DownloadOptions {
+//!     destination: "lectures".into(),
+//!     jobs: 2,
+//!     retry_policy,
+//!     verbosity: 0,
+//!     exclusive_folders: template.has_course_folders(),
+//! };
+//! download::download_course(options, course, None, Arc::new(Silent), &session).await?;
+//! # Ok(())
+//! # }
+//! ```
+
+#![warn(missing_docs)]
+
+pub mod checksum;
+pub mod constants;
+pub mod download;
+pub mod error;
+pub mod extractor;
+pub mod feed;
+pub mod filter;
+pub mod playlist;
+pub mod retry;
+pub mod sanitize;
+pub mod search;
+pub mod session;
+pub mod state;
+pub mod target;
+pub mod template;
+pub mod types;
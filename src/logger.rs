@@ -0,0 +1,30 @@
+//! Shows the messages the library logs on stderr.
+
+use log::{Level, LevelFilter, Log, Metadata, Record};
+
+/// Prints the messages of the library as they are, without levels or timestamps
+struct StderrLogger;
+
+impl Log for StderrLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        // The HTTP crates are too chatty
+        metadata.level() <= Level::Info && metadata.target().starts_with("tube_get")
+    }
+
+    fn log(&self, record: &Record) {
+        if self.enabled(record.metadata()) {
+            eprintln!("{}", record.args());
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+/// Installs the logger, which is done once at startup
+pub fn init() {
+    static LOGGER: StderrLogger = StderrLogger;
+
+    if log::set_logger(&LOGGER).is_ok() {
+        log::set_max_level(LevelFilter::Info);
+    }
+}
@@ -1,60 +1,280 @@
+use std::fs;
+use std::path::Path;
+use std::process::ExitCode;
 use std::sync::Arc;
 
-use anyhow::Result;
+use anyhow::{anyhow, Result};
+use cli::{CliOptions, Login};
 use indicatif::MultiProgress;
+use progress::ActiveBars;
+use reqwest::Url;
+use tube_get::error::{self, Error};
+use tube_get::extractor::Course;
+use tube_get::session::{Session, SessionBuilder};
+use tube_get::target::Target;
+use tube_get::{
+    checksum, constants, download, extractor, feed, filter, playlist, search, state, template,
+};
 
 mod cli;
-mod constants;
-mod download;
-mod extractor;
-mod types;
+mod config;
+mod logger;
+mod output;
+mod progress;
+mod prompt;
+mod report;
 
 #[tokio::main]
-async fn main() -> Result<()> {
+async fn main() -> ExitCode {
+    match run().await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            // Printed like an error returned from `main`, but with an exit code telling its kind
+            eprintln!("Error: {:?}", err);
+            ExitCode::from(error::exit_code(&err))
+        }
+    }
+}
+
+async fn run() -> Result<()> {
     // The working directory
     let pwd = std::env::current_dir()
-        .unwrap()
-        .to_str()
-        .unwrap()
-        .to_owned();
+        .map_err(|err| Error::filesystem("access the working directory", ".", err))?
+        .to_string_lossy()
+        .into_owned();
 
     // Parse the command line parameters into arg-matches
     let matches = cli::configure_parser(&pwd).get_matches();
 
+    // Show what the library reports while it works
+    logger::init();
+
     // Print the name and version of the application along its license notice
-    println!("{} {}", constants::NAME, constants::VERSION);
-    println!("{}\n", constants::LICENSE);
+    eprintln!("{} {}", constants::NAME, constants::VERSION);
+    eprintln!("{}\n", constants::LICENSE);
+
+    // Search for series instead of downloading them
+    if let Some(("search", matches)) = matches.subcommand() {
+        let config = config::Config::load(matches.value_of("config"))?;
+        let login = cli::get_login(matches, &config, "query", false)?;
+        let search_options = cli::get_search_options(matches, &config, &login)?;
+
+        let session = build_session(&search_options.server, login, ActiveBars::default()).await?;
+        let found = search::search_series(&search_options, &session).await?;
+        report::print_series(&found);
+        return Ok(());
+    }
+
+    // Write a podcast feed instead of downloading the series
+    if let Some(("feed", matches)) = matches.subcommand() {
+        let config = config::Config::load(matches.value_of("config"))?;
+        let login = cli::get_login(matches, &config, "series", true)?;
+        let feed_options = cli::get_feed_options(matches, &config, &login)?;
+
+        let session = build_session(&feed_options.server, login, ActiveBars::default()).await?;
+        let feed = feed::build_feed(&feed_options, &session).await?;
+
+        match matches.value_of("output").map(Path::new) {
+            Some(path) => {
+                fs::write(path, &feed.xml)
+                    .map_err(|err| Error::filesystem("write the feed", path, err))?;
+                eprintln!("Wrote feed {} ({} items)", path.display(), feed.items);
+            }
+            None => print!("{}", feed.xml),
+        }
+
+        return Ok(());
+    }
 
     // Try to extract the desired configuration from the arg-matches
-    let cli_options = cli::get_options(&matches)?;
+    let config = config::Config::load(matches.value_of("config"))?;
+    // Targets may also come from a series file instead
+    let targets_required = !matches.is_present("series_file");
+    let login = cli::get_login(&matches, &config, "UUID", targets_required)?;
+    let cli_options = cli::get_options(&matches, &config, login)?;
+
+    // Shared with the token prompt, which hides them while asking
+    let bars = ActiveBars::default();
+    let session = build_session(
+        &cli_options.server,
+        cli_options.login.to_owned(),
+        bars.clone(),
+    )
+    .await?;
+
+    // Process every series, even if some of them fail
+    let mut summary = Vec::new();
+    let mut courses = Vec::new();
+    for &target in &cli_options.targets {
+        let result = process_target(&cli_options, &session, &bars, target, &mut courses).await;
+
+        if let Err(err) = &result {
+            eprintln!("Failed to process {}: {}\n", target, err);
+        }
+
+        summary.push((target, result));
+    }
+
+    if summary.len() > 1 {
+        eprintln!("\nSummary:");
+        for (target, result) in &summary {
+            match result {
+                Ok(title) => eprintln!("  {} ({}): ok", title, target),
+                Err(err) => eprintln!("  {}: failed: {}", target, err),
+            }
+        }
+    }
+
+    if cli_options.no_download {
+        output::print_courses(&courses, cli_options.format)?;
+    }
+
+    if let Some(playlist) = &cli_options.playlist {
+        let entries = playlist::write_playlist(
+            &playlist.path,
+            playlist.format,
+            &courses,
+            &cli_options.destination,
+            playlist.remote,
+        )?;
+        eprintln!(
+            "Wrote playlist {} ({} entries)",
+            playlist.path.display(),
+            entries
+        );
+    }
 
-    let client = extractor::make_client(&cli_options.token)?;
+    let total = summary.len();
+    let mut errors = summary
+        .into_iter()
+        .filter_map(|(_, result)| result.err())
+        .collect::<Vec<_>>();
+
+    match (errors.len(), total) {
+        (0, _) => Ok(()),
+        (_, 1) => Err(errors.remove(0)),
+        (failed, total) => Err(error::combine(
+            format!("{} of {} downloads failed", failed, total),
+            errors,
+        )),
+    }
+}
+
+/// Logs in to the server. Once the session expires, we log in again with the password,
+/// or ask for a fresh token (when run in a terminal).
+async fn build_session(server: &Url, login: Login, bars: ActiveBars) -> Result<Session> {
+    let builder = SessionBuilder::new(server.to_owned());
+
+    match login {
+        Login::Token(token) => {
+            let builder = builder.token(&token);
+
+            match prompt::TokenPrompt::if_interactive(bars) {
+                Some(prompt) => builder.renewer(prompt).build(),
+                None => builder.build(),
+            }
+        }
+        Login::Password(credentials) => {
+            eprintln!("Logging in as {}...", credentials.username);
+
+            let session = builder.credentials(credentials.to_owned()).build()?;
+            session.login(&credentials).await?;
+            Ok(session)
+        }
+    }
+}
 
+/// Crawls (and downloads, depending on the options) a series or episode, returning its title.
+///
+/// The crawled course is added to `courses` (once it was downloaded, if it should be).
+async fn process_target(
+    cli_options: &CliOptions,
+    session: &Session,
+    bars: &ActiveBars,
+    target: Target,
+    courses: &mut Vec<Course>,
+) -> Result<String> {
     let episodes_data = extractor::get_episodes(
-        &client,
-        cli_options.skip_count.unwrap_or(0),
-        cli_options.limit_count.unwrap_or(99999),
-        cli_options.uuid,
+        session,
+        &cli_options.server,
+        target,
+        constants::PAGE_SIZE,
+        cli_options.retry_policy,
         cli_options.verbosity,
     )
     .await?;
 
-    let course = extractor::extract_course_data(&episodes_data)?;
+    let mut course = extractor::extract_course_data(&episodes_data, &cli_options.track_selection)?;
+    template::assign_paths(
+        &mut course,
+        &cli_options.output_template,
+        cli_options.sanitize_mode,
+    );
+    let title = course.title.to_owned();
+
+    // Only now, so the videos are numbered and named as in the whole series
+    extractor::select_episodes(
+        &mut course,
+        target,
+        cli_options.skip_count.unwrap_or(0),
+        cli_options.limit_count,
+    );
+
+    // Apply the file & path filters and matchers
+    let (course, excluded) =
+        filter::filter_course(course, &cli_options.filters, &cli_options.destination);
+    report::print_excluded(&excluded);
+
+    // Load the state of previous runs (if requested)
+    let mut state_store = cli_options
+        .state_store_path
+        .as_ref()
+        .map(state::StateStore::load)
+        .transpose()?;
 
-    if !cli_options.no_download {
+    if cli_options.verify {
+        eprintln!("Verifying course {}", course.title);
+        let checks = checksum::verify_course(&cli_options.destination, &course).await?;
+
+        match report::print_checks(&checks) {
+            0 => eprintln!("Verification complete."),
+            failures => return Err(anyhow!("{} file(s) are missing or corrupt", failures)),
+        }
+        courses.push(course);
+    } else if !cli_options.no_download {
         let multi_bar = Arc::new(MultiProgress::new());
-        let jh = tokio::spawn(download::download_course(
-            cli_options,
-            course,
+        let progress_bars = progress::ProgressBars::new(
             Arc::clone(&multi_bar),
-            &client,
+            &course,
+            cli_options.skip_count.unwrap_or(0) as usize,
+        );
+        bars.set(Some(Arc::clone(&multi_bar)));
+
+        let jh = tokio::spawn(download::download_course(
+            cli_options.download_options(),
+            course.to_owned(),
+            state_store,
+            Arc::new(progress_bars),
+            session,
         ));
 
-        multi_bar.join()?;
+        let joined = multi_bar.join();
+        bars.set(None);
+        joined?;
         jh.await??;
+        courses.push(course);
     } else {
-        println!("{:#?}", course);
+        // Remember the crawled videos, so a later run can download them
+        if let Some(store) = state_store.as_mut() {
+            course
+                .videos
+                .iter()
+                .for_each(|video| store.add_pending(video));
+            store.save()?;
+        }
+
+        courses.push(course);
     }
 
-    Ok(())
+    Ok(title)
 }
@@ -0,0 +1,157 @@
+//! Prints crawled courses as listings (JSON, NDJSON, CSV or a table).
+
+use anyhow::Result;
+use serde::Serialize;
+
+use std::io::{self, Write};
+use std::str::FromStr;
+
+use tube_get::error::Error;
+use tube_get::extractor::{Course, Video};
+
+/// The format of the crawl listing (printed to stdout with `--no-download`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// A JSON array of all courses
+    Json,
+    /// One JSON object per video (and line)
+    Ndjson,
+    /// One row per video, with a header
+    Csv,
+    /// A human-readable table
+    Table,
+}
+
+/// A video along with the course it belongs to (for the line-based formats)
+#[derive(Serialize)]
+struct Record<'a> {
+    course_id: &'a str,
+    course_title: &'a str,
+    #[serde(flatten)]
+    video: &'a Video,
+}
+
+/// The columns of the CSV format
+const CSV_HEADER: [&str; 9] = [
+    "course_id",
+    "course_title",
+    "id",
+    "track_id",
+    "title",
+    "video_type",
+    "quality",
+    "url",
+    "checksum",
+];
+
+/// Prints the crawled courses to stdout
+pub fn print_courses(courses: &[Course], format: OutputFormat) -> Result<()> {
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+
+    match format {
+        OutputFormat::Json => {
+            serde_json::to_writer_pretty(&mut out, courses)?;
+            writeln!(out)?;
+        }
+        OutputFormat::Ndjson => {
+            for (course, video) in videos(courses) {
+                serde_json::to_writer(&mut out, &record(course, video))?;
+                writeln!(out)?;
+            }
+        }
+        OutputFormat::Csv => {
+            writeln!(out, "{}", CSV_HEADER.join(","))?;
+
+            for (course, video) in videos(courses) {
+                let row = [
+                    course.id.to_owned(),
+                    course.title.to_owned(),
+                    video.id.to_owned(),
+                    video.track_id.to_owned(),
+                    video.title.to_owned(),
+                    video.video_type.to_string(),
+                    video.quality.to_owned().unwrap_or_default(),
+                    video.url.to_owned(),
+                    video
+                        .checksum
+                        .as_ref()
+                        .map(|checksum| format!("{}:{}", checksum.type_field, checksum.field))
+                        .unwrap_or_default(),
+                ];
+
+                let row = row.iter().map(|field| csv_field(field)).collect::<Vec<_>>();
+                writeln!(out, "{}", row.join(","))?;
+            }
+        }
+        OutputFormat::Table => {
+            for course in courses {
+                writeln!(out, "{} ({})", course.title, course.id)?;
+
+                let width = course.videos.len().to_string().len();
+
+                for (index, video) in course.videos.iter().enumerate() {
+                    writeln!(
+                        out,
+                        "  {:>width$}  {:<18}  {:<6}  {}",
+                        index + 1,
+                        video.video_type.to_string(),
+                        video.quality.as_deref().unwrap_or("-"),
+                        video.title,
+                    )?;
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn videos(courses: &[Course]) -> impl Iterator<Item = (&Course, &Video)> {
+    courses
+        .iter()
+        .flat_map(|course| course.videos.iter().map(move |video| (course, video)))
+}
+
+fn record<'a>(course: &'a Course, video: &'a Video) -> Record<'a> {
+    Record {
+        course_id: &course.id,
+        course_title: &course.title,
+        video,
+    }
+}
+
+/// Quotes a CSV field if necessary (RFC 4180)
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_owned()
+    }
+}
+
+impl FromStr for OutputFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Error> {
+        match s {
+            "json" => Ok(OutputFormat::Json),
+            "ndjson" => Ok(OutputFormat::Ndjson),
+            "csv" => Ok(OutputFormat::Csv),
+            "table" => Ok(OutputFormat::Table),
+            _ => Err(Error::Input(format!("Unknown output format: {}", s))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_csv_field() {
+        assert_eq!(csv_field("Lecture 1"), "Lecture 1");
+        assert_eq!(csv_field("Lecture 1, part 2"), "\"Lecture 1, part 2\"");
+        assert_eq!(csv_field("The \"best\" one"), "\"The \"\"best\"\" one\"");
+    }
+}
@@ -0,0 +1,191 @@
+//! Writes M3U and XSPF playlists of crawled courses.
+
+use anyhow::{anyhow, Result};
+use html_escape::encode_text;
+use reqwest::Url;
+
+use std::fmt::Write;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use crate::download;
+use crate::error::Error;
+use crate::extractor::{Course, Video};
+
+/// The format of a playlist
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlaylistFormat {
+    /// An extended M3U playlist
+    M3u,
+    /// An XML Shareable Playlist Format playlist
+    Xspf,
+}
+
+impl PlaylistFormat {
+    /// Guesses the format from the file extension (defaulting to M3U)
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some(extension) if extension.eq_ignore_ascii_case("xspf") => PlaylistFormat::Xspf,
+            _ => PlaylistFormat::M3u,
+        }
+    }
+}
+
+/// The playlist options given on the command line
+#[derive(Debug, Clone)]
+pub struct PlaylistOptions {
+    /// Where to write the playlist to
+    pub path: PathBuf,
+    /// The format of the playlist
+    pub format: PlaylistFormat,
+    /// Point at the remote URLs instead of the local files
+    pub remote: bool,
+}
+
+/// A single entry of a playlist
+struct Entry<'a> {
+    /// The remote URL or the local path of the video
+    location: String,
+    title: String,
+    course: &'a str,
+    /// The duration in milliseconds
+    duration: i64,
+}
+
+/// Writes a playlist of the courses, with the videos of each course in lecture order.
+///
+/// The entries point at the local files, or at the remote URLs (for streaming) if `remote` is set.
+/// Returns the number of entries.
+pub fn write_playlist(
+    path: &Path,
+    format: PlaylistFormat,
+    courses: &[Course],
+    destination: &Path,
+    remote: bool,
+) -> Result<usize> {
+    // Local files are referred to relative to the playlist (if possible)
+    let playlist_dir = std::env::current_dir()?.join(path.parent().unwrap_or(Path::new("")));
+
+    let mut entries = Vec::new();
+
+    for course in courses {
+        let mut videos = course.videos.iter().collect::<Vec<&Video>>();
+        videos.sort_by(|a, b| a.start.cmp(&b.start));
+
+        for video in videos {
+            let location = if remote {
+                video.url.to_owned()
+            } else {
+                let file_path = download::video_path(destination, video);
+                local_location(&file_path, &playlist_dir, format)?
+            };
+
+            entries.push(Entry {
+                location,
+                title: format!("{} ({})", video.title, video.video_type),
+                course: &course.title,
+                duration: video.duration,
+            });
+        }
+    }
+
+    let text = match format {
+        PlaylistFormat::M3u => m3u(&entries)?,
+        PlaylistFormat::Xspf => xspf(&entries)?,
+    };
+
+    fs::write(path, text).map_err(|err| Error::filesystem("write the playlist", path, err))?;
+
+    Ok(entries.len())
+}
+
+/// Where a playlist finds a local file (XSPF needs URIs, M3U takes plain paths)
+fn local_location(file_path: &Path, playlist_dir: &Path, format: PlaylistFormat) -> Result<String> {
+    let absolute = std::env::current_dir()?.join(file_path);
+
+    Ok(match format {
+        PlaylistFormat::M3u => match absolute.strip_prefix(playlist_dir) {
+            Ok(relative) => relative.display().to_string(),
+            Err(_) => absolute.display().to_string(),
+        },
+        PlaylistFormat::Xspf => Url::from_file_path(&absolute)
+            .map_err(|_| anyhow!("Invalid path {}", absolute.display()))?
+            .to_string(),
+    })
+}
+
+fn m3u(entries: &[Entry]) -> Result<String> {
+    let mut text = String::from("#EXTM3U\n");
+
+    for entry in entries {
+        // Line breaks would end the entry early
+        let title = entry.title.replace(['\r', '\n'], " ");
+
+        writeln!(text, "#EXTINF:{},{}", entry.duration / 1000, title)?;
+        writeln!(text, "{}", entry.location)?;
+    }
+
+    Ok(text)
+}
+
+fn xspf(entries: &[Entry]) -> Result<String> {
+    let mut text = String::from(concat!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n",
+        "<playlist version=\"1\" xmlns=\"http://xspf.org/ns/0/\">\n",
+        "  <trackList>\n"
+    ));
+
+    for entry in entries {
+        text.push_str("    <track>\n");
+        writeln!(
+            text,
+            "      <location>{}</location>",
+            encode_text(&entry.location)
+        )?;
+        writeln!(text, "      <title>{}</title>", encode_text(&entry.title))?;
+        writeln!(text, "      <album>{}</album>", encode_text(entry.course))?;
+        writeln!(text, "      <duration>{}</duration>", entry.duration)?;
+        text.push_str("    </track>\n");
+    }
+
+    text.push_str("  </trackList>\n</playlist>\n");
+    Ok(text)
+}
+
+impl FromStr for PlaylistFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Error> {
+        match s {
+            "m3u" => Ok(PlaylistFormat::M3u),
+            "xspf" => Ok(PlaylistFormat::Xspf),
+            _ => Err(Error::Input(format!("Unknown playlist format: {}", s))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_playlists() {
+        let entries = [Entry {
+            location: "https://example.org/video.mp4?a=1&b=2".to_owned(),
+            title: "Lecture <1>".to_owned(),
+            course: "Analysis",
+            duration: 5_400_000,
+        }];
+
+        assert_eq!(
+            m3u(&entries).unwrap(),
+            "#EXTM3U\n#EXTINF:5400,Lecture <1>\nhttps://example.org/video.mp4?a=1&b=2\n"
+        );
+
+        let xspf = xspf(&entries).unwrap();
+        assert!(xspf.contains("<location>https://example.org/video.mp4?a=1&amp;b=2</location>"));
+        assert!(xspf.contains("<title>Lecture &lt;1&gt;</title>"));
+        assert!(xspf.contains("<duration>5400000</duration>"));
+    }
+}
@@ -0,0 +1,157 @@
+use indicatif::{
+    HumanBytes, HumanDuration, MultiProgress, ProgressBar, ProgressDrawTarget, ProgressStyle,
+};
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+use tube_get::download::{DownloadObserver, Downloaded};
+use tube_get::extractor::{Course, Video};
+
+/// Renders the progress of the downloads of a course as progress bars
+pub struct ProgressBars {
+    multi_bar: Arc<MultiProgress>,
+    /// Counts the finished videos
+    main_pb: ProgressBar,
+    /// The bars of the running transfers, by episode and track id
+    bars: Mutex<HashMap<(String, String), ProgressBar>>,
+    /// The number of finished videos (including the skipped ones), used for the "x/y" numbering
+    count: AtomicUsize,
+    total: usize,
+}
+
+impl ProgressBars {
+    /// Adds the bars for a course, whose first `skipped` videos were skipped by the user
+    pub fn new(multi_bar: Arc<MultiProgress>, course: &Course, skipped: usize) -> Self {
+        let main_pb = multi_bar.add(ProgressBar::new(course.videos.len() as u64));
+
+        main_pb.set_style(ProgressStyle::default_bar().template(
+            &(String::new() + "[{elapsed_precise}] {wide_bar} Total progress: {pos}/{len}"),
+        ));
+
+        // Make the main progress bar render immediately rather than waiting for the first task to finish.
+        main_pb.tick();
+
+        ProgressBars {
+            multi_bar,
+            main_pb,
+            bars: Mutex::new(HashMap::new()),
+            count: AtomicUsize::new(skipped),
+            total: course.videos.len() + skipped,
+        }
+    }
+}
+
+/// The progress bars currently on screen (if any), for those who need the terminal for a while
+#[derive(Clone, Default)]
+pub struct ActiveBars(Arc<Mutex<Option<Arc<MultiProgress>>>>);
+
+impl ActiveBars {
+    /// Remembers the bars of the course being downloaded (`None` once it is done)
+    pub fn set(&self, multi_bar: Option<Arc<MultiProgress>>) {
+        *self.0.lock().unwrap() = multi_bar;
+    }
+
+    /// Hides the bars while running `f`, so they don't draw over e.g. a prompt
+    pub fn suspend<T>(&self, f: impl FnOnce() -> T) -> T {
+        let multi_bar = self.0.lock().unwrap().clone();
+
+        if let Some(multi_bar) = &multi_bar {
+            multi_bar.set_draw_target(ProgressDrawTarget::hidden());
+        }
+
+        let result = f();
+
+        if let Some(multi_bar) = &multi_bar {
+            multi_bar.set_draw_target(ProgressDrawTarget::stderr());
+        }
+
+        result
+    }
+}
+
+fn key(video: &Video) -> (String, String) {
+    (video.id.to_owned(), video.track_id.to_owned())
+}
+
+impl DownloadObserver for ProgressBars {
+    fn course_started(&self, course: &Course) {
+        self.main_pb
+            .println(format!("Downloading course {}", course.title));
+    }
+
+    fn message(&self, message: &str) {
+        self.main_pb.println(message);
+    }
+
+    fn transfer_started(&self, video: &Video, offset: u64, size: Option<u64>) {
+        let progress_bar = self.multi_bar.add(
+            ProgressBar::new(size.unwrap_or(0))
+                .with_style(
+                    ProgressStyle::default_bar()
+                        .template(
+                            "[{elapsed_precise}] {wide_bar:.cyan} {bytes}/{total_bytes} ({percent}%) - ETA {eta_precise} with {binary_bytes_per_sec} - {msg}"
+                        )
+                        .progress_chars("█▉▊▋▌▍▎▏  "),
+                )
+                .with_message(video.title.to_owned()),
+        );
+
+        progress_bar.set_position(offset);
+        progress_bar.reset_eta();
+
+        self.bars.lock().unwrap().insert(key(video), progress_bar);
+    }
+
+    fn transfer_progress(&self, video: &Video, bytes: u64) {
+        if let Some(progress_bar) = self.bars.lock().unwrap().get(&key(video)) {
+            progress_bar.inc(bytes);
+        }
+
+        self.main_pb.tick();
+    }
+
+    fn transfer_finished(&self, video: &Video) {
+        if let Some(progress_bar) = self.bars.lock().unwrap().remove(&key(video)) {
+            progress_bar.finish_and_clear();
+        }
+    }
+
+    fn video_finished(&self, video: &Video, downloaded: Option<&Downloaded>) {
+        let count = self.count.fetch_add(1, Ordering::SeqCst) + 1;
+
+        match downloaded {
+            None => self.main_pb.println(format!(
+                "  Skipping {:2}/{:2}, already downloaded: {}",
+                count, self.total, video.title
+            )),
+            Some(downloaded) => {
+                let transferred = HumanBytes(downloaded.transferred);
+                let elapsed = HumanDuration(downloaded.elapsed);
+
+                self.main_pb.println(format!(
+                    "  Finished {:2}/{:2}, {transferred} in {elapsed}: {}",
+                    count, self.total, video.title
+                ))
+            }
+        }
+
+        self.main_pb.inc(1);
+    }
+
+    fn course_finished(&self, _course: &Course, failures: &[(String, anyhow::Error)]) {
+        if failures.is_empty() {
+            self.main_pb.finish();
+            eprintln!("Download complete.");
+            return;
+        }
+
+        self.main_pb.abandon();
+
+        eprintln!("Failed to download {} video(s):", failures.len());
+        for (title, err) in failures {
+            eprintln!("  {}: {}", title, err);
+        }
+    }
+}
@@ -0,0 +1,48 @@
+//! Asks the user for a fresh login token once the session expired during a run.
+
+use anyhow::Result;
+
+use std::io::{self, BufRead, IsTerminal, Write};
+
+use tube_get::error::Error;
+use tube_get::session::{Renew, RenewFuture, Session};
+
+use crate::progress::ActiveBars;
+
+/// Asks for a recent `JSESSIONID` cookie on the terminal (hiding the progress bars meanwhile)
+pub struct TokenPrompt {
+    bars: ActiveBars,
+}
+
+impl TokenPrompt {
+    /// The prompt, unless there's no one to ask (i.e. stdin isn't a terminal)
+    pub fn if_interactive(bars: ActiveBars) -> Option<Self> {
+        io::stdin().is_terminal().then_some(TokenPrompt { bars })
+    }
+}
+
+impl Renew for TokenPrompt {
+    fn renew<'a>(&'a self, session: &'a Session) -> RenewFuture<'a> {
+        Box::pin(async move {
+            let bars = self.bars.clone();
+            let token = tokio::task::spawn_blocking(move || bars.suspend(read_token)).await??;
+            session.set_token(&token);
+            Ok(())
+        })
+    }
+}
+
+/// Reads a token from stdin, failing if the user doesn't enter one
+fn read_token() -> Result<String> {
+    eprintln!("\nYour login token has expired.");
+    eprint!("Please paste a recent JSESSIONID cookie (or press Enter to give up): ");
+    io::stderr().flush()?;
+
+    let mut line = String::new();
+    io::stdin().lock().read_line(&mut line)?;
+
+    match line.trim() {
+        "" => Err(Error::AuthExpired.into()),
+        token => Ok(token.to_owned()),
+    }
+}
@@ -0,0 +1,65 @@
+//! Prints the results of searches, checks and filters for the user.
+
+use tube_get::checksum::{FileCheck, Verification};
+use tube_get::constants;
+use tube_get::extractor::Video;
+use tube_get::filter::FilterReason;
+use tube_get::search::FoundSeries;
+
+/// Prints which videos were filtered out (and why)
+pub fn print_excluded(excluded: &[(Video, FilterReason)]) {
+    if excluded.is_empty() {
+        return;
+    }
+
+    eprintln!("Filtered out {} video(s):", excluded.len());
+
+    for (video, reason) in excluded {
+        eprintln!(
+            "  {} [{}] ({}): {}",
+            video.title, video.video_type, video.id, reason
+        );
+    }
+}
+
+/// Prints the series found along with their UUIDs and episode counts
+pub fn print_series(found: &[FoundSeries]) {
+    eprintln!("Found {} series:", found.len());
+
+    for series in found {
+        let episodes = series
+            .episodes
+            .map_or_else(|| "?".to_owned(), |count| count.to_string());
+
+        println!(
+            "  {}  {:>4} episode(s)  {}",
+            series.id, episodes, series.title
+        );
+
+        if !series.lecturers.is_empty() {
+            println!("      by {}", series.lecturers.join(", "));
+        }
+    }
+
+    if found.len() > constants::MAX_EPISODE_COUNTS {
+        eprintln!(
+            "Only counted the episodes of the first {} series, narrow down the search for the others",
+            constants::MAX_EPISODE_COUNTS
+        );
+    }
+}
+
+/// Prints how the files of a course compare to their checksums, returning the number of failures
+pub fn print_checks(checks: &[FileCheck]) -> usize {
+    for check in checks {
+        let status = match &check.verification {
+            Some(Verification::Match) => "ok".to_owned(),
+            Some(verification) => verification.to_string(),
+            None => "missing".to_owned(),
+        };
+
+        println!("  {}: {}", check.path.display(), status);
+    }
+
+    checks.iter().filter(|check| check.is_failure()).count()
+}
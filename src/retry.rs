@@ -0,0 +1,185 @@
+//! Retries failed requests with exponential backoff.
+
+use anyhow::{anyhow, Result};
+use rand::Rng;
+use reqwest::header::RETRY_AFTER;
+use reqwest::{RequestBuilder, Response, StatusCode};
+
+use std::fmt::{self, Display};
+use std::time::Duration;
+
+use crate::error::Error;
+
+/// The delay before the first retry (doubled for every further one)
+const BASE_DELAY: Duration = Duration::from_secs(1);
+
+/// The maximum delay between two attempts
+const MAX_DELAY: Duration = Duration::from_secs(60);
+
+/// How often (and how patiently) to retry failed requests
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// The maximum number of attempts per request (including the first one)
+    pub max_attempts: u32,
+}
+
+impl RetryPolicy {
+    /// The exponential backoff (with jitter) before the given retry (starting at 1)
+    pub fn delay(&self, retry: u32) -> Duration {
+        let delay = BASE_DELAY
+            .saturating_mul(2u32.saturating_pow(retry.saturating_sub(1)))
+            .min(MAX_DELAY);
+
+        // Wait somewhere between half and all of the delay, so parallel requests don't line up
+        delay.mul_f64(rand::thread_rng().gen_range(0.5..=1.0))
+    }
+}
+
+/// Sends a request, retrying on connection errors and on responses with a transient status.
+///
+/// `Retry-After` headers are honoured (capped to a minute). The response of the last attempt
+/// is returned as-is, even if its status is an error.
+pub async fn send(
+    policy: RetryPolicy,
+    request: RequestBuilder,
+    on_retry: impl Fn(String),
+) -> Result<Response> {
+    let mut attempt = 1;
+
+    loop {
+        // Requests with streaming bodies can't be cloned (we never send those)
+        let result = request
+            .try_clone()
+            .ok_or_else(|| anyhow!("Request can't be retried"))?
+            .send()
+            .await;
+
+        if attempt >= policy.max_attempts {
+            return Ok(result?);
+        }
+
+        let delay = match &result {
+            Ok(response) if is_transient_status(response.status()) => {
+                let delay = retry_after(response).unwrap_or_else(|| policy.delay(attempt));
+                on_retry(format!(
+                    "Got {}, retrying in {:.1}s (attempt {}/{})",
+                    response.status(),
+                    delay.as_secs_f64(),
+                    attempt + 1,
+                    policy.max_attempts
+                ));
+                delay
+            }
+            Err(err) if is_transient_error(err) => {
+                let delay = policy.delay(attempt);
+                on_retry(format!(
+                    "{}, retrying in {:.1}s (attempt {}/{})",
+                    err,
+                    delay.as_secs_f64(),
+                    attempt + 1,
+                    policy.max_attempts
+                ));
+                delay
+            }
+            _ => return Ok(result?),
+        };
+
+        tokio::time::sleep(delay).await;
+        attempt += 1;
+    }
+}
+
+/// The context of an error for a transient status, telling how long the server asked us to wait
+/// (in a `Retry-After` header) before trying again
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryAfter {
+    /// The status the server answered with
+    pub status: StatusCode,
+    /// How long to wait
+    pub delay: Duration,
+}
+
+impl Display for RetryAfter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Got {}, asked to wait {:.1}s",
+            self.status,
+            self.delay.as_secs_f64()
+        )
+    }
+}
+
+/// The delay the server asked for before retrying after an error, if it did
+pub fn requested_delay(err: &anyhow::Error) -> Option<Duration> {
+    err.downcast_ref::<RetryAfter>()
+        .map(|retry_after| retry_after.delay)
+}
+
+/// Whether an error (of any step of a download) is worth retrying, including transient statuses
+/// turned into errors (e.g. by `error_for_status`) and connections closed early
+pub fn is_transient(err: &anyhow::Error) -> bool {
+    err.chain().any(|cause| {
+        cause
+            .downcast_ref::<reqwest::Error>()
+            .is_some_and(is_transient_error)
+            || matches!(
+                cause.downcast_ref::<Error>(),
+                Some(Error::Incomplete { .. })
+            )
+    })
+}
+
+fn is_transient_error(err: &reqwest::Error) -> bool {
+    err.is_timeout()
+        || err.is_connect()
+        || err.is_request()
+        || err.is_body()
+        || err.status().is_some_and(is_transient_status)
+}
+
+fn is_transient_status(status: StatusCode) -> bool {
+    status == StatusCode::TOO_MANY_REQUESTS
+        || status == StatusCode::REQUEST_TIMEOUT
+        || status.is_server_error()
+}
+
+/// Parses the `Retry-After` header of a 429 or 503 response (in seconds or as an HTTP date)
+pub(crate) fn retry_after(response: &Response) -> Option<Duration> {
+    if response.status() != StatusCode::TOO_MANY_REQUESTS
+        && response.status() != StatusCode::SERVICE_UNAVAILABLE
+    {
+        return None;
+    }
+
+    let value = response.headers().get(RETRY_AFTER)?.to_str().ok()?.trim();
+
+    let delay = match value.parse::<u64>() {
+        Ok(seconds) => Duration::from_secs(seconds),
+        Err(_) => chrono::DateTime::parse_from_rfc2822(value)
+            .ok()?
+            .signed_duration_since(chrono::Utc::now())
+            .to_std()
+            .unwrap_or(Duration::ZERO),
+    };
+
+    Some(delay.min(MAX_DELAY))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_delay() {
+        let policy = RetryPolicy { max_attempts: 10 };
+
+        for retry in 1..10 {
+            let expected = BASE_DELAY * 2u32.pow(retry - 1);
+            let delay = policy.delay(retry);
+
+            assert!(delay <= expected.min(MAX_DELAY));
+            assert!(delay >= expected.min(MAX_DELAY) / 2);
+        }
+    }
+}
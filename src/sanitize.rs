@@ -0,0 +1,182 @@
+//! Makes titles safe to use as file names on POSIX and Windows file systems.
+
+use std::str::FromStr;
+
+use crate::error::Error;
+
+/// The maximum length of a file name in bytes (255 on common file systems), leaving room for
+/// the `.part` extension and a ` (n)` suffix for colliding names
+const MAX_NAME_BYTES: usize = 240;
+
+/// The longest extension that is kept when shortening a file name
+const MAX_EXTENSION_BYTES: usize = 16;
+
+/// The characters Windows (and thus NTFS and SMB shares) doesn't allow in file names
+const WINDOWS_RESERVED_CHARS: [char; 9] = ['<', '>', ':', '"', '/', '\\', '|', '?', '*'];
+
+/// The device names Windows doesn't allow as file names (even with an extension),
+/// including the ones with superscript digits
+const WINDOWS_RESERVED_NAMES: [&str; 30] = [
+    "CON", "PRN", "AUX", "NUL", "COM0", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7",
+    "COM8", "COM9", "COM¹", "COM²", "COM³", "LPT0", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6",
+    "LPT7", "LPT8", "LPT9", "LPT¹", "LPT²", "LPT³",
+];
+
+/// Which file systems the file names have to be valid on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SanitizeMode {
+    /// Only replaces what POSIX file systems don't allow (slashes and null bytes)
+    Posix,
+    /// Also handles the restrictions of Windows, NTFS and SMB shares
+    Windows,
+}
+
+impl SanitizeMode {
+    /// The mode matching the platform we run on
+    pub fn native() -> Self {
+        if cfg!(windows) {
+            SanitizeMode::Windows
+        } else {
+            SanitizeMode::Posix
+        }
+    }
+
+    /// Whether the file system treats names which only differ in case as the same file
+    pub fn is_case_insensitive(self) -> bool {
+        self == SanitizeMode::Windows
+    }
+}
+
+/// Makes a single file or folder name safe to use.
+///
+/// Forbidden and control characters are replaced by `_` and overly long names are shortened
+/// (on a character boundary, keeping the extension).
+pub fn sanitize_name(name: &str, mode: SanitizeMode) -> String {
+    let name = name
+        .chars()
+        .map(|c| match c {
+            '/' | '\0' => '_',
+            c if c.is_control() => '_',
+            c if mode == SanitizeMode::Windows && WINDOWS_RESERVED_CHARS.contains(&c) => '_',
+            c => c,
+        })
+        .collect::<String>();
+
+    // Shortened first, as that may leave trailing dots or spaces behind
+    let mut name = shorten(&name, MAX_NAME_BYTES);
+
+    if mode == SanitizeMode::Windows {
+        // Windows silently drops trailing dots and spaces
+        name.truncate(name.trim_end_matches(['.', ' ']).len());
+
+        let stem = name.split('.').next().unwrap_or_default().trim_end();
+        if WINDOWS_RESERVED_NAMES
+            .iter()
+            .any(|reserved| reserved.eq_ignore_ascii_case(stem))
+        {
+            name.insert(0, '_');
+        }
+    }
+
+    if name.is_empty() || name == "." || name == ".." {
+        name = "_".to_owned();
+    }
+
+    name
+}
+
+/// Shortens a name to at most `max_bytes` bytes of UTF-8, keeping (short) extensions
+fn shorten(name: &str, max_bytes: usize) -> String {
+    if name.len() <= max_bytes {
+        return name.to_owned();
+    }
+
+    let (stem, extension) = split_extension(name);
+    let extension = if extension.len() <= MAX_EXTENSION_BYTES {
+        extension
+    } else {
+        ""
+    };
+
+    format!(
+        "{}{}",
+        truncate(stem, max_bytes - extension.len()),
+        extension
+    )
+}
+
+/// Truncates a string to at most `max_bytes` bytes, without splitting a character
+fn truncate(text: &str, max_bytes: usize) -> &str {
+    let mut end = max_bytes.min(text.len());
+
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+
+    &text[..end]
+}
+
+/// Splits a file name into its stem and its extension (including the dot)
+fn split_extension(name: &str) -> (&str, &str) {
+    match name.rfind('.') {
+        Some(dot) if dot > 0 => name.split_at(dot),
+        _ => (name, ""),
+    }
+}
+
+/// Adds a numbered suffix to a file name, e.g. `Lecture (2).mp4`
+pub fn number_name(name: &str, number: usize) -> String {
+    let (stem, extension) = split_extension(name);
+    format!("{} ({}){}", stem, number, extension)
+}
+
+impl FromStr for SanitizeMode {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Error> {
+        match s {
+            "posix" => Ok(SanitizeMode::Posix),
+            "windows" => Ok(SanitizeMode::Windows),
+            _ => Err(Error::Input(format!("Unknown sanitize mode: {}", s))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_sanitize_name() {
+        let posix = |name: &str| sanitize_name(name, SanitizeMode::Posix);
+        let windows = |name: &str| sanitize_name(name, SanitizeMode::Windows);
+
+        assert_eq!(posix("Lecture 1/2: Intro?"), "Lecture 1_2: Intro?");
+        assert_eq!(windows("Lecture 1/2: Intro?"), "Lecture 1_2_ Intro_");
+        assert_eq!(windows("Notes\t*draft*. ."), "Notes__draft_");
+        assert_eq!(windows("con.mp4"), "_con.mp4");
+        assert_eq!(windows("COM0.mp4"), "_COM0.mp4");
+        assert_eq!(windows("lpt0"), "_lpt0");
+        assert_eq!(windows("COM³.txt"), "_COM³.txt");
+        assert_eq!(windows("lpt¹"), "_lpt¹");
+        assert_eq!(posix("COM0.mp4"), "COM0.mp4");
+        assert_eq!(posix(".."), "_");
+        assert_eq!(windows("..."), "_");
+
+        // Shortened on a character boundary, keeping the extension
+        let long = posix(&format!("{}.mp4", "ä".repeat(200)));
+        assert_eq!(long.len(), MAX_NAME_BYTES);
+        assert!(long.ends_with("ä.mp4"));
+
+        // Shortening doesn't leave trailing spaces behind on Windows
+        let long = windows(&format!(
+            "{} {}",
+            "a".repeat(MAX_NAME_BYTES - 1),
+            "b".repeat(20)
+        ));
+        assert_eq!(long, "a".repeat(MAX_NAME_BYTES - 1));
+
+        assert_eq!(number_name("Lecture.mp4", 2), "Lecture (2).mp4");
+        assert_eq!(number_name(".hidden", 3), ".hidden (3)");
+    }
+}
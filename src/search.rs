@@ -0,0 +1,173 @@
+//! Searches for series by title, lecturer and term.
+
+use anyhow::Result;
+use reqwest::Url;
+use serde_json::Value;
+use uuid::Uuid;
+
+use crate::constants;
+use crate::extractor;
+use crate::retry::RetryPolicy;
+use crate::session::Session;
+use crate::types::series;
+
+/// What to search for
+#[derive(Debug, Clone)]
+pub struct SearchOptions {
+    /// The base URL of the Opencast server
+    pub server: Url,
+    /// Text the titles have to contain
+    pub query: Option<String>,
+    /// Text the name of a lecturer has to contain
+    pub lecturer: Option<String>,
+    /// The term (e.g. "WS 2021") the series has to be held in
+    pub term: Option<String>,
+    /// How often to retry the requests to the API
+    pub retry_policy: RetryPolicy,
+    /// How many details to report (0 is the least)
+    pub verbosity: u64,
+}
+
+/// A series matching the search
+#[derive(Debug, Clone)]
+pub struct FoundSeries {
+    /// The id of the series (usually a UUID)
+    pub id: String,
+    /// The title of the series
+    pub title: String,
+    /// The creators and contributors of the series
+    pub lecturers: Vec<String>,
+    /// The number of episodes, if they were counted (see [`constants::MAX_EPISODE_COUNTS`])
+    pub episodes: Option<i64>,
+}
+
+/// Searches for series, returning the matching ones along with their episode counts
+pub async fn search_series(options: &SearchOptions, session: &Session) -> Result<Vec<FoundSeries>> {
+    let data = extractor::get_series(
+        session,
+        &options.server,
+        options.query.as_deref(),
+        options.retry_policy,
+        options.verbosity,
+    )
+    .await?;
+
+    let mut found = Vec::new();
+
+    for series in data
+        .search_results
+        .result
+        .iter()
+        .filter(|series| is_match(series, options))
+    {
+        // Other Opencast installations may use ids which aren't UUIDs
+        let uuid = series.id.parse::<Uuid>().ok();
+
+        let episodes = match uuid.filter(|_| found.len() < constants::MAX_EPISODE_COUNTS) {
+            Some(uuid) => Some(
+                extractor::get_episode_count(
+                    session,
+                    &options.server,
+                    uuid,
+                    options.retry_policy,
+                    options.verbosity,
+                )
+                .await?,
+            ),
+            None => None,
+        };
+
+        found.push(FoundSeries {
+            id: series.id.to_owned(),
+            title: series.dc_title.to_owned(),
+            lecturers: lecturers(series).into_iter().map(str::to_owned).collect(),
+            episodes,
+        });
+    }
+
+    Ok(found)
+}
+
+/// Checks a series against the title, lecturer and term given by the user (case-insensitively)
+fn is_match(series: &series::Result, options: &SearchOptions) -> bool {
+    let contains = |text: &str, part: &str| text.to_lowercase().contains(&part.to_lowercase());
+
+    let title = options
+        .query
+        .as_ref()
+        .map_or(true, |query| contains(&series.dc_title, query));
+
+    let lecturer = options.lecturer.as_ref().map_or(true, |lecturer| {
+        lecturers(series)
+            .iter()
+            .any(|name| contains(name, lecturer))
+    });
+
+    let term = options.term.as_ref().map_or(true, |term| {
+        [
+            Some(&series.dc_title),
+            series.dc_temporal.as_ref(),
+            series.dc_created.as_ref(),
+        ]
+        .into_iter()
+        .flatten()
+        .any(|text| contains(text, term))
+    });
+
+    title && lecturer && term
+}
+
+/// The creators and contributors of a series
+fn lecturers(series: &series::Result) -> Vec<&str> {
+    let mut lecturers = Vec::new();
+
+    // Skip the numbers the API mixes in
+    for name in series
+        .dc_creator
+        .iter()
+        .chain(&series.dc_contributor)
+        .filter_map(Value::as_str)
+    {
+        if !lecturers.contains(&name) {
+            lecturers.push(name);
+        }
+    }
+
+    lecturers
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::retry::RetryPolicy;
+
+    #[test]
+    fn test_is_match() {
+        let series = series::Result {
+            id: "series".to_owned(),
+            dc_title: "Analysis T1 (WS 2021/22)".to_owned(),
+            dc_creator: vec!["Jane Doe".into(), 0.into()],
+            dc_contributor: vec!["John Roe".into()],
+            ..Default::default()
+        };
+
+        let options =
+            |query: Option<&str>, lecturer: Option<&str>, term: Option<&str>| SearchOptions {
+                server: "https://tube.tugraz.at/".parse().unwrap(),
+                query: query.map(str::to_owned),
+                lecturer: lecturer.map(str::to_owned),
+                term: term.map(str::to_owned),
+                retry_policy: RetryPolicy { max_attempts: 1 },
+                verbosity: 0,
+            };
+
+        assert!(is_match(&series, &options(None, None, None)));
+        assert!(is_match(
+            &series,
+            &options(Some("analysis"), Some("roe"), Some("WS 2021"))
+        ));
+        assert!(!is_match(&series, &options(Some("Algebra"), None, None)));
+        assert!(!is_match(&series, &options(None, Some("Smith"), None)));
+        assert!(!is_match(&series, &options(None, None, Some("SS 2022"))));
+    }
+}
@@ -0,0 +1,337 @@
+//! The login session with the Opencast server, which is renewed when it expires during a run.
+
+use anyhow::Result;
+use reqwest::header::CONTENT_TYPE;
+use reqwest::{cookie::Jar, Client, Response, StatusCode, Url};
+use serde_json::Value;
+use tokio::sync::Mutex;
+
+use std::fmt::{self, Debug};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+
+use crate::constants;
+use crate::error::Error;
+
+/// The future returned by [`Renew::renew`]
+pub type RenewFuture<'a> = Pin<Box<dyn Future<Output = Result<()>> + Send + 'a>>;
+
+/// Gets a fresh session once the current one expired (e.g. by asking the user for a new token)
+pub trait Renew: Send + Sync {
+    /// Renews the session, e.g. with [`Session::set_token`]
+    fn renew<'a>(&'a self, session: &'a Session) -> RenewFuture<'a>;
+}
+
+/// A username and password for the form login of Opencast
+#[derive(Clone)]
+pub struct Credentials {
+    /// The username
+    pub username: String,
+    /// The password (left out of the `Debug` output)
+    pub password: String,
+}
+
+/// Logs in again with the same credentials
+impl Renew for Credentials {
+    fn renew<'a>(&'a self, session: &'a Session) -> RenewFuture<'a> {
+        Box::pin(session.login(self))
+    }
+}
+
+impl Debug for Credentials {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Credentials")
+            .field("username", &self.username)
+            .finish_non_exhaustive()
+    }
+}
+
+/// An HTTP client for an Opencast server, along with its cookies and a way to renew its login.
+///
+/// Cloning it is cheap, and the clones share their cookies.
+#[derive(Clone)]
+pub struct Session {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    client: Client,
+    jar: Arc<Jar>,
+    server: Url,
+    renewer: Option<Box<dyn Renew>>,
+    /// Cleared once renewing failed, so we don't keep on trying for every request
+    renewable: AtomicBool,
+    /// How often the session was renewed so far
+    generation: AtomicU64,
+    /// Held while renewing, so parallel requests finding the session expired only renew it once
+    renewing: Mutex<()>,
+}
+
+impl Session {
+    /// The HTTP client, which sends the cookies of the session along
+    pub fn client(&self) -> &Client {
+        &self.inner.client
+    }
+
+    /// The base URL of the Opencast server
+    pub fn server(&self) -> &Url {
+        &self.inner.server
+    }
+
+    /// Logs in with a session token (the `JSESSIONID` cookie)
+    pub fn set_token(&self, token: &str) {
+        self.inner.jar.add_cookie_str(
+            &format!("{}={}", constants::SESSION_COOKIE, token.trim()),
+            &self.inner.server,
+        );
+    }
+
+    /// Logs in with a username and password, storing the new session cookie.
+    ///
+    /// Opencast redirects back to its login page if it doesn't accept them.
+    pub async fn login(&self, credentials: &Credentials) -> Result<()> {
+        let url = self.inner.server.join(constants::LOGIN_PATH)?;
+
+        let response = self
+            .inner
+            .client
+            .post(url)
+            .form(&[
+                ("j_username", credentials.username.as_str()),
+                ("j_password", credentials.password.as_str()),
+            ])
+            .send()
+            .await?;
+
+        let final_url = response.url();
+        if response.status() == StatusCode::UNAUTHORIZED
+            || final_url.path().contains("login")
+            || final_url
+                .query()
+                .is_some_and(|query| query.contains("error"))
+        {
+            return Err(Error::LoginFailed(credentials.username.to_owned()).into());
+        }
+
+        response.error_for_status()?;
+        Ok(())
+    }
+
+    /// Like [`check_response`], but also finds the session expired if the server forbade a request
+    /// and [`Session::is_logged_in`] agrees.
+    ///
+    /// A 403 on its own may just mean that the user isn't allowed to see this one video.
+    pub async fn check_access(&self, response: &Response) -> Result<()> {
+        check_response(response)?;
+
+        if response.status() == StatusCode::FORBIDDEN && !self.is_logged_in().await? {
+            return Err(Error::AuthExpired.into());
+        }
+
+        Ok(())
+    }
+
+    /// Asks the server whether the session is still logged in, going by the roles of its user.
+    ///
+    /// Assumes it is if the server doesn't tell.
+    pub async fn is_logged_in(&self) -> Result<bool> {
+        let url = self.inner.server.join(constants::ME_PATH)?;
+        let response = self.inner.client.get(url).send().await?;
+
+        if check_response(&response).is_err() {
+            return Ok(false);
+        }
+
+        let me = match response.error_for_status() {
+            Ok(response) => response.json::<Value>().await.unwrap_or_default(),
+            Err(_) => return Ok(true),
+        };
+
+        // Anonymous users only have the anonymous role
+        Ok(me["roles"].as_array().map_or(true, |roles| {
+            roles.iter().any(|role| role != constants::ANONYMOUS_ROLE)
+        }))
+    }
+
+    /// Sends requests until they no longer find the session expired, renewing it in between.
+    ///
+    /// Gives up after a few renewals, or right away if the session can't be renewed.
+    pub async fn with_renewal<T, F>(&self, mut request: impl FnMut() -> F) -> Result<T>
+    where
+        F: Future<Output = Result<T>>,
+    {
+        let mut renewals = 0;
+
+        loop {
+            let generation = self.inner.generation.load(Ordering::SeqCst);
+
+            match request().await {
+                Err(err)
+                    if is_auth_expired(&err)
+                        && self.inner.renewable.load(Ordering::SeqCst)
+                        && renewals < constants::MAX_RENEWALS =>
+                {
+                    renewals += 1;
+                    self.renew(generation).await?;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// Renews the session, unless another request already did since `generation`
+    async fn renew(&self, generation: u64) -> Result<()> {
+        let _renewing = self.inner.renewing.lock().await;
+
+        if self.inner.generation.load(Ordering::SeqCst) != generation {
+            return Ok(());
+        }
+
+        // Another request already failed to renew it while we waited
+        if !self.inner.renewable.load(Ordering::SeqCst) {
+            return Err(Error::AuthExpired.into());
+        }
+
+        if let Some(renewer) = &self.inner.renewer {
+            if let Err(err) = renewer.renew(self).await {
+                self.inner.renewable.store(false, Ordering::SeqCst);
+                return Err(err);
+            }
+        }
+
+        self.inner.generation.fetch_add(1, Ordering::SeqCst);
+        Ok(())
+    }
+}
+
+/// Builds the [`Session`] for an Opencast server
+pub struct SessionBuilder {
+    server: Url,
+    token: Option<String>,
+    user_agent: String,
+    renewer: Option<Box<dyn Renew>>,
+}
+
+impl SessionBuilder {
+    /// Starts a session for the server with the given base URL, which isn't logged in yet
+    pub fn new(server: Url) -> Self {
+        SessionBuilder {
+            server,
+            token: None,
+            user_agent: format!("{}/{}", constants::NAME, constants::VERSION),
+            renewer: None,
+        }
+    }
+
+    /// Logs in with a session token (the `JSESSIONID` cookie)
+    pub fn token(mut self, token: &str) -> Self {
+        self.token = Some(token.to_owned());
+        self
+    }
+
+    /// The user agent to send (defaults to the name and version of this crate)
+    pub fn user_agent(mut self, user_agent: &str) -> Self {
+        self.user_agent = user_agent.to_owned();
+        self
+    }
+
+    /// Logs in again with a username and password (see [`Session::login`]) whenever the
+    /// session expires
+    pub fn credentials(self, credentials: Credentials) -> Self {
+        self.renewer(credentials)
+    }
+
+    /// How to renew the session once it expires (it isn't renewed if this isn't set)
+    pub fn renewer(mut self, renewer: impl Renew + 'static) -> Self {
+        self.renewer = Some(Box::new(renewer));
+        self
+    }
+
+    /// Builds the session, which fails if the HTTP client can't be set up
+    pub fn build(self) -> Result<Session> {
+        let jar = Arc::new(Jar::default());
+
+        let client = Client::builder()
+            .cookie_provider(Arc::clone(&jar))
+            .user_agent(self.user_agent)
+            .build()?;
+
+        let session = Session {
+            inner: Arc::new(Inner {
+                client,
+                jar,
+                server: self.server,
+                renewable: AtomicBool::new(self.renewer.is_some()),
+                renewer: self.renewer,
+                generation: AtomicU64::new(0),
+                renewing: Mutex::new(()),
+            }),
+        };
+
+        if let Some(token) = &self.token {
+            session.set_token(token);
+        }
+
+        Ok(session)
+    }
+}
+
+/// Fails with [`Error::AuthExpired`] if the server rejected a request for lack of a valid session.
+///
+/// Opencast answers those with a 401, or by redirecting to its (HTML) login page. A 403 can also
+/// mean the user lacks access to the resource, which is up to [`Session::check_access`].
+pub fn check_response(response: &Response) -> Result<(), Error> {
+    let status = response.status();
+
+    let login_page = response.url().path().contains("login");
+    let html = response
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.trim_start().starts_with("text/html"));
+
+    if status == StatusCode::UNAUTHORIZED || (status.is_success() && (login_page || html)) {
+        Err(Error::AuthExpired)
+    } else {
+        Ok(())
+    }
+}
+
+/// Whether an error (or one of its causes) is an expired session
+pub fn is_auth_expired(err: &anyhow::Error) -> bool {
+    err.chain()
+        .any(|cause| matches!(cause.downcast_ref::<Error>(), Some(Error::AuthExpired)))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_check_response() {
+        let response = |status: u16, content_type: &str| {
+            Response::from(
+                hyper::Response::builder()
+                    .status(status)
+                    .header(CONTENT_TYPE, content_type)
+                    .body("")
+                    .unwrap(),
+            )
+        };
+
+        assert!(check_response(&response(200, "application/json")).is_ok());
+        assert!(check_response(&response(206, "video/mp4")).is_ok());
+        assert!(check_response(&response(404, "text/html")).is_ok());
+        assert!(matches!(
+            check_response(&response(200, "text/html; charset=utf-8")),
+            Err(Error::AuthExpired)
+        ));
+        assert!(matches!(
+            check_response(&response(401, "application/json")),
+            Err(Error::AuthExpired)
+        ));
+        assert!(check_response(&response(403, "application/json")).is_ok());
+    }
+}
@@ -0,0 +1,153 @@
+//! Persists the download state of crawled videos between runs.
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::error::Error;
+use crate::extractor::Video;
+
+/// The persistent state of a crawl, as stored in the `--store-state` file
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct StateStore {
+    /// Where the state is read from and written to
+    #[serde(skip)]
+    path: PathBuf,
+    /// Maps episode ids to their tracks (by track id) and their download states
+    episodes: BTreeMap<String, BTreeMap<String, TrackState>>,
+}
+
+/// The download state of a single track
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum TrackState {
+    /// Crawled, but not yet downloaded
+    Pending,
+    /// The download was interrupted after `bytes` bytes
+    Partial {
+        /// The size of the `.part` file
+        bytes: u64,
+    },
+    /// The download finished successfully
+    Completed {
+        /// The size of the file
+        bytes: u64,
+    },
+    /// The download failed before any data was written
+    Failed {
+        /// Why it failed
+        error: String,
+    },
+}
+
+impl StateStore {
+    /// Loads the state from a file, or starts with an empty state if the file doesn't exist yet
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref().to_owned();
+
+        let mut store: StateStore = if path.exists() {
+            let text = fs::read_to_string(&path)
+                .map_err(|err| Error::filesystem("read the state file", &path, err))?;
+
+            // Most likely the file given isn't a state file at all
+            serde_json::from_str(&text).map_err(|err| {
+                Error::Input(format!(
+                    "Could not parse the state file {}: {}",
+                    path.display(),
+                    err
+                ))
+            })?
+        } else {
+            StateStore::default()
+        };
+
+        store.path = path;
+        Ok(store)
+    }
+
+    /// Writes the state to its file (via a temporary file, so a crash never leaves half a state)
+    pub fn save(&self) -> Result<()> {
+        let temp_path = self.path.with_extension("tmp");
+
+        fs::write(&temp_path, serde_json::to_string_pretty(self)?)
+            .map_err(|err| Error::filesystem("write the state file", &temp_path, err))?;
+        fs::rename(&temp_path, &self.path)
+            .map_err(|err| Error::filesystem("write the state file", &self.path, err))?;
+
+        Ok(())
+    }
+
+    /// Gets the state of a video's track, if it is known
+    pub fn get(&self, video: &Video) -> Option<&TrackState> {
+        self.episodes
+            .get(&video.id)
+            .and_then(|tracks| tracks.get(&video.track_id))
+    }
+
+    /// Sets the state of a video's track
+    pub fn set(&mut self, video: &Video, state: TrackState) {
+        self.episodes
+            .entry(video.id.to_owned())
+            .or_default()
+            .insert(video.track_id.to_owned(), state);
+    }
+
+    /// Records a video as pending, unless its state is already known
+    pub fn add_pending(&mut self, video: &Video) {
+        self.episodes
+            .entry(video.id.to_owned())
+            .or_default()
+            .entry(video.track_id.to_owned())
+            .or_insert(TrackState::Pending);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::types::episodes::TrackType;
+
+    #[test]
+    fn test_state_round_trip() {
+        let path = std::env::temp_dir().join(format!("tube-get-state-{}.json", std::process::id()));
+        let video = Video {
+            url: "https://example.org/video.mp4".to_owned(),
+            title: "Lecture 1".to_owned(),
+            id: "episode".to_owned(),
+            track_id: "track".to_owned(),
+            video_type: TrackType::Presenter,
+            quality: None,
+            checksum: None,
+            start: "2021-10-01T08:15:00Z".to_owned(),
+            duration: 5_400_000,
+            mimetype: "video/mp4".to_owned(),
+            index: 1,
+            presenters: Vec::new(),
+            path: PathBuf::new(),
+        };
+
+        let mut store = StateStore::load(&path).unwrap();
+        assert_eq!(store.get(&video), None);
+
+        store.add_pending(&video);
+        store.set(&video, TrackState::Completed { bytes: 42 });
+        store.add_pending(&video);
+        store.save().unwrap();
+
+        let store = StateStore::load(&path).unwrap();
+        assert_eq!(
+            store.get(&video),
+            Some(&TrackState::Completed { bytes: 42 })
+        );
+
+        // A file that isn't a state file is the user's mistake
+        fs::write(&path, "Not a state").unwrap();
+        let err = StateStore::load(&path).unwrap_err();
+        assert_eq!(crate::error::exit_code(&err), crate::error::EXIT_INPUT);
+
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,130 @@
+//! The series and episodes to crawl, given as UUIDs or URLs.
+
+use reqwest::Url;
+use uuid::Uuid;
+
+use std::fmt::Display;
+use std::str::FromStr;
+
+use crate::error::Error;
+
+/// What to crawl & download: a whole series or a single episode
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Target {
+    /// All episodes of the series with this id
+    Series(Uuid),
+    /// The episode with this id
+    Episode(Uuid),
+}
+
+impl Target {
+    /// Parses a bare UUID (as the given kind of target), or a URL pointing at a series or an episode
+    pub fn parse_as(s: &str, bare: fn(Uuid) -> Target) -> Result<Self, Error> {
+        let s = s.trim();
+
+        if let Ok(uuid) = s.parse() {
+            return Ok(bare(uuid));
+        }
+
+        let url =
+            Url::parse(s).map_err(|_| Error::Input(format!("Neither a UUID nor a URL: {}", s)))?;
+
+        from_url(&url).ok_or_else(|| {
+            Error::Input(format!("Could not find a series or episode id in {}", url))
+        })
+    }
+
+    /// The query parameter of the episode search API selecting the target
+    pub fn query(&self) -> String {
+        match self {
+            Target::Series(uuid) => format!("sid={uuid}"),
+            Target::Episode(uuid) => format!("id={uuid}"),
+        }
+    }
+}
+
+/// Parses a bare series UUID, or a (TUbe/Opencast) URL pointing at a series or an episode
+impl FromStr for Target {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Error> {
+        Target::parse_as(s, Target::Series)
+    }
+}
+
+fn from_url(url: &Url) -> Option<Target> {
+    let parse = |value: &str| value.parse::<Uuid>().ok();
+
+    // Player & series pages, e.g. `/paella/ui/watch.html?id=<episode>` or `...?sid=<series>`
+    for (key, value) in url.query_pairs() {
+        match key.as_ref() {
+            "id" => return parse(&value).map(Target::Episode),
+            "sid" | "series" | "seriesId" | "series_id" => {
+                return parse(&value).map(Target::Series)
+            }
+            _ => {}
+        }
+    }
+
+    // Paths, e.g. `/play/<episode>` or `.../series/<series>`
+    let segments = url.path_segments()?.collect::<Vec<_>>();
+    segments.windows(2).find_map(|pair| match pair {
+        ["play", id] => parse(id).map(Target::Episode),
+        ["series", id] => parse(id).map(Target::Series),
+        _ => None,
+    })
+}
+
+impl Display for Target {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Target::Series(uuid) => write!(f, "series {uuid}"),
+            Target::Episode(uuid) => write!(f, "episode {uuid}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_target() {
+        let uuid: Uuid = "2fe6ca52-5b9a-4b8c-b5a4-9a1c1e8c7d11".parse().unwrap();
+
+        let parse = |s: &str| s.parse::<Target>().ok();
+
+        assert_eq!(parse(&uuid.to_string()), Some(Target::Series(uuid)));
+        assert_eq!(
+            Target::parse_as(&uuid.to_string(), Target::Episode).ok(),
+            Some(Target::Episode(uuid))
+        );
+        assert_eq!(
+            parse(&format!(
+                "https://tube.tugraz.at/paella/ui/watch.html?id={uuid}"
+            )),
+            Some(Target::Episode(uuid))
+        );
+        assert_eq!(
+            parse(&format!(
+                "https://tube.tugraz.at/engage/ui/index.html?sid={uuid}&limit=8"
+            )),
+            Some(Target::Series(uuid))
+        );
+        assert_eq!(
+            parse(&format!("https://opencast.example.org/play/{uuid}")),
+            Some(Target::Episode(uuid))
+        );
+        assert_eq!(
+            parse(&format!(
+                "https://opencast.example.org/ltitools/series/{uuid}/"
+            )),
+            Some(Target::Series(uuid))
+        );
+        assert_eq!(
+            parse("https://tube.tugraz.at/paella/ui/watch.html?id=nope"),
+            None
+        );
+        assert_eq!(parse("not a uuid"), None);
+    }
+}
@@ -0,0 +1,385 @@
+//! Templates for the paths of the downloaded files.
+
+use chrono::format::{Item, StrftimeItems};
+use chrono::DateTime;
+
+use std::collections::HashSet;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use crate::error::Error;
+use crate::extractor::{Course, Video};
+use crate::sanitize::{self, SanitizeMode};
+use crate::types::episodes::TrackType;
+
+/// The date format used by `{date}` without an explicit format
+const DEFAULT_DATE_FORMAT: &str = "%Y-%m-%d";
+
+/// A template for the paths of the downloaded files (relative to the destination), e.g.
+/// `{series}/{date:%Y-%m-%d} {index:02} {title} [{type}].{ext}`.
+///
+/// Literal braces are written as `{{` and `}}`. Only the slashes of the template itself create
+/// folders, the names containing substituted values are sanitized.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Template {
+    parts: Vec<Part>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Part {
+    Literal(String),
+    Field(Field),
+}
+
+/// A placeholder of the template
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Field {
+    /// The title of the series (or of the episode, if it has no series)
+    Series,
+    SeriesId,
+    /// The title of the episode
+    Title,
+    /// The id of the episode
+    Id,
+    TrackId,
+    /// The start of the recording, in the given `strftime` format
+    Date(String),
+    /// The chronological position of the episode in its series, zero-padded to a width
+    Index(usize),
+    /// The duration, e.g. `1h30m05s`
+    Duration,
+    /// The presenters, separated by commas
+    Presenter,
+    /// The track type, e.g. `presenter`
+    Type,
+    /// The track flavor, e.g. `presenter/delivery`
+    Flavor,
+    /// The quality tag, e.g. `high`
+    Quality,
+    /// The file extension belonging to the mimetype, e.g. `mp4`
+    Ext,
+}
+
+impl Template {
+    /// Renders the path of a video of a course
+    pub fn render(&self, course: &Course, video: &Video, mode: SanitizeMode) -> PathBuf {
+        // The names of the path, along with whether they contain substituted values
+        let mut names = vec![(String::new(), false)];
+
+        for part in &self.parts {
+            match part {
+                Part::Literal(text) => {
+                    let mut literals = text.split('/');
+                    let (name, _) = names.last_mut().unwrap();
+                    name.push_str(literals.next().unwrap_or_default());
+                    names.extend(literals.map(|literal| (literal.to_owned(), false)));
+                }
+                Part::Field(field) => {
+                    let (name, substituted) = names.last_mut().unwrap();
+                    name.push_str(&field.value(course, video));
+                    *substituted = true;
+                }
+            }
+        }
+
+        // Names written by the user (like `..`) are kept as they are
+        names
+            .iter()
+            .map(|(name, substituted)| match substituted {
+                true => sanitize::sanitize_name(name, mode),
+                false => name.to_owned(),
+            })
+            .collect::<Vec<_>>()
+            .join("/")
+            .into()
+    }
+
+    /// Whether every course gets a folder of its own, which nothing else is downloaded to.
+    ///
+    /// That's the case if the series is part of the first folder (i.e. followed by a `/`),
+    /// not if it is only part of the file names.
+    pub fn has_course_folders(&self) -> bool {
+        let mut series = false;
+
+        for part in &self.parts {
+            match part {
+                Part::Field(Field::Series | Field::SeriesId) => series = true,
+                Part::Literal(text) if text.contains('/') => return series,
+                _ => {}
+            }
+        }
+
+        false
+    }
+}
+
+/// Assigns the paths of the videos of a course.
+///
+/// Colliding paths are numbered in the order the episodes were recorded (e.g. `Lecture (2).mp4`),
+/// so the same crawl always yields the same names.
+pub fn assign_paths(course: &mut Course, template: &Template, mode: SanitizeMode) {
+    let mut order = (0..course.videos.len()).collect::<Vec<_>>();
+    order.sort_by(|&a, &b| {
+        let (a, b) = (&course.videos[a], &course.videos[b]);
+        (a.index, &a.track_id).cmp(&(b.index, &b.track_id))
+    });
+
+    let mut taken = HashSet::new();
+
+    for i in order {
+        let path = template.render(course, &course.videos[i], mode);
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+
+        let key = |path: &PathBuf| match mode.is_case_insensitive() {
+            true => path.to_string_lossy().to_lowercase(),
+            false => path.to_string_lossy().into_owned(),
+        };
+
+        let mut unique = path.to_owned();
+        let mut number = 2;
+
+        while !taken.insert(key(&unique)) {
+            unique = path.with_file_name(sanitize::number_name(&name, number));
+            number += 1;
+        }
+
+        course.videos[i].path = unique;
+    }
+}
+
+impl Field {
+    fn parse(name: &str, format: Option<&str>) -> Result<Self, Error> {
+        let field = match (name, format) {
+            ("date", format) => {
+                let format = format.unwrap_or(DEFAULT_DATE_FORMAT);
+
+                if StrftimeItems::new(format).any(|item| item == Item::Error) {
+                    return Err(Error::Input(format!(
+                        "Invalid date format in the template: {}",
+                        format
+                    )));
+                }
+
+                Field::Date(format.to_owned())
+            }
+            ("index", format) => Field::Index(match format {
+                Some(width) => width.parse().map_err(|_| {
+                    Error::Input(format!("Invalid index width in the template: {}", width))
+                })?,
+                None => 0,
+            }),
+            (_, Some(format)) => {
+                return Err(Error::Input(format!(
+                    "The placeholder {{{}}} does not take a format (got {})",
+                    name, format
+                )))
+            }
+            ("series", None) => Field::Series,
+            ("series_id", None) => Field::SeriesId,
+            ("title", None) => Field::Title,
+            ("id", None) => Field::Id,
+            ("track_id", None) => Field::TrackId,
+            ("duration", None) => Field::Duration,
+            ("presenter", None) => Field::Presenter,
+            ("type", None) => Field::Type,
+            ("flavor", None) => Field::Flavor,
+            ("quality", None) => Field::Quality,
+            ("ext", None) => Field::Ext,
+            _ => {
+                return Err(Error::Input(format!(
+                    "Unknown placeholder in the template: {{{}}}",
+                    name
+                )))
+            }
+        };
+
+        Ok(field)
+    }
+
+    fn value(&self, course: &Course, video: &Video) -> String {
+        match self {
+            Field::Series => course.title.to_owned(),
+            Field::SeriesId => course.id.to_owned(),
+            Field::Title => video.title.to_owned(),
+            Field::Id => video.id.to_owned(),
+            Field::TrackId => video.track_id.to_owned(),
+            Field::Date(format) => match DateTime::parse_from_rfc3339(&video.start) {
+                Ok(start) => start.format(format).to_string(),
+                Err(_) => video.start.to_owned(),
+            },
+            Field::Index(width) => format!("{:0width$}", video.index, width = width),
+            Field::Duration => {
+                let seconds = video.duration.max(0) / 1000;
+                format!(
+                    "{}h{:02}m{:02}s",
+                    seconds / 3600,
+                    seconds / 60 % 60,
+                    seconds % 60
+                )
+            }
+            Field::Presenter if video.presenters.is_empty() => "unknown".to_owned(),
+            Field::Presenter => video.presenters.join(", "),
+            Field::Type => video.video_type.to_string(),
+            Field::Flavor => flavor(video.video_type).to_owned(),
+            Field::Quality => video.quality.as_deref().unwrap_or("unknown").to_owned(),
+            Field::Ext => extension(&video.mimetype).to_owned(),
+        }
+    }
+}
+
+/// The flavor of a track type, as used by the API
+fn flavor(track_type: TrackType) -> &'static str {
+    match track_type {
+        TrackType::Presenter => "presenter/delivery",
+        TrackType::PresenterNoAudio => "presenter_video/delivery",
+        TrackType::Presentation => "presentation/delivery",
+        TrackType::Raw => "raw/delivery",
+    }
+}
+
+/// The usual file extension of a mimetype
+fn extension(mimetype: &str) -> &str {
+    match mimetype {
+        "video/x-matroska" => "mkv",
+        "video/quicktime" => "mov",
+        "audio/mpeg" => "mp3",
+        "audio/mp4" => "m4a",
+        _ => mimetype.rsplit('/').next().unwrap_or(mimetype),
+    }
+}
+
+impl FromStr for Template {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Error> {
+        let mut parts = Vec::new();
+        let mut literal = String::new();
+        let mut chars = s.chars();
+
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.as_str().starts_with('{') => {
+                    chars.next();
+                    literal.push('{');
+                }
+                '}' if chars.as_str().starts_with('}') => {
+                    chars.next();
+                    literal.push('}');
+                }
+                '{' => {
+                    let rest = chars.as_str();
+                    let end = rest.find('}').ok_or_else(|| {
+                        Error::Input(format!("Unclosed placeholder in the template: {}", s))
+                    })?;
+
+                    let (name, format) = match rest[..end].split_once(':') {
+                        Some((name, format)) => (name, Some(format)),
+                        None => (&rest[..end], None),
+                    };
+
+                    if !literal.is_empty() {
+                        parts.push(Part::Literal(std::mem::take(&mut literal)));
+                    }
+                    parts.push(Part::Field(Field::parse(name.trim(), format)?));
+
+                    chars = rest[end + 1..].chars();
+                }
+                '}' => return Err(Error::Input(format!("Unmatched }} in the template: {}", s))),
+                c => literal.push(c),
+            }
+        }
+
+        if !literal.is_empty() {
+            parts.push(Part::Literal(literal));
+        }
+
+        Ok(Template { parts })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_render_template() {
+        let video = Video {
+            url: "https://example.org/video.mp4".to_owned(),
+            title: "Lecture 1/2".to_owned(),
+            id: "episode".to_owned(),
+            track_id: "track".to_owned(),
+            video_type: TrackType::Presenter,
+            quality: Some("high".to_owned()),
+            checksum: None,
+            start: "2021-10-01T08:15:00Z".to_owned(),
+            duration: 5_405_000,
+            mimetype: "video/mp4".to_owned(),
+            index: 3,
+            presenters: vec!["Jane Doe".to_owned()],
+            path: PathBuf::new(),
+        };
+        let course = Course {
+            title: "Analysis".to_owned(),
+            id: "series".to_owned(),
+            videos: Vec::new(),
+            partial: false,
+        };
+
+        let render = |template: &str| {
+            template
+                .parse::<Template>()
+                .unwrap()
+                .render(&course, &video, SanitizeMode::Windows)
+        };
+
+        assert_eq!(
+            render("{series}/{date:%Y-%m-%d} {index:02} {title} [{type}].{ext}"),
+            PathBuf::from("Analysis/2021-10-01 03 Lecture 1_2 [presenter].mp4")
+        );
+        assert_eq!(
+            render("{{{presenter}}} {duration} {quality} {flavor}"),
+            PathBuf::from("{Jane Doe} 1h30m05s high presenter_delivery")
+        );
+        assert_eq!(
+            render("../{date:%H:%M} {title}."),
+            PathBuf::from("../08_15 Lecture 1_2")
+        );
+
+        // Colliding paths are numbered in the order the episodes were recorded
+        let mut course = Course {
+            videos: vec![
+                Video {
+                    index: 4,
+                    ..video.to_owned()
+                },
+                video.to_owned(),
+            ],
+            ..course
+        };
+        let template = "{title}.{ext}".parse::<Template>().unwrap();
+        assign_paths(&mut course, &template, SanitizeMode::Posix);
+
+        assert_eq!(course.videos[0].path, PathBuf::from("Lecture 1_2 (2).mp4"));
+        assert_eq!(course.videos[1].path, PathBuf::from("Lecture 1_2.mp4"));
+
+        assert!("{series}/{title}"
+            .parse::<Template>()
+            .unwrap()
+            .has_course_folders());
+        assert!(!"{title}/{series}"
+            .parse::<Template>()
+            .unwrap()
+            .has_course_folders());
+        assert!(!"{series} {title}"
+            .parse::<Template>()
+            .unwrap()
+            .has_course_folders());
+        assert!("{series_id}_{series}/{title}"
+            .parse::<Template>()
+            .unwrap()
+            .has_course_folders());
+        assert!("{unknown}".parse::<Template>().is_err());
+        assert!("{title:02}".parse::<Template>().is_err());
+        assert!("{title".parse::<Template>().is_err());
+    }
+}
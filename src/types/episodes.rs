@@ -1,79 +1,121 @@
+use serde::de::value::{MapAccessDeserializer, SeqAccessDeserializer};
+use serde::de::{self, IntoDeserializer, MapAccess, SeqAccess, Visitor};
 use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::Value;
 
+use std::fmt;
+use std::marker::PhantomData;
+
+/// The response of an episode search
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct EpisodesData {
+    /// The (page of) episodes found
     #[serde(rename = "search-results")]
     pub search_results: SearchResults,
 }
 
+/// A page of search results
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct SearchResults {
+    /// The number of results before this page
     pub offset: i64,
+    /// The maximum number of results on a page
     pub limit: i64,
+    /// The number of results on all pages
     pub total: i64,
+    /// How long the search took (in milliseconds)
     pub search_time: i64,
+    /// The search as the server understood it (e.g. `sid:<series id>`)
     pub query: String,
-    // #[serde(deserialize_with = "one_or_more_result")]
+    /// The episodes on this page
+    #[serde(default, deserialize_with = "one_or_more")]
     pub result: Vec<Result>,
 }
 
-fn one_or_more_result<'de, D>(deserializer: D) -> std::result::Result<Vec<Result>, D::Error>
+/// De-serializes a list which the API sends as a single element if it has only one,
+/// and leaves out entirely (or sends as `null`) if it is empty
+pub(crate) fn one_or_more<'de, D, T>(deserializer: D) -> std::result::Result<Vec<T>, D::Error>
 where
     D: Deserializer<'de>,
+    T: Deserialize<'de>,
 {
-    #[derive(Deserialize)]
-    #[serde(untagged)]
-    // TODO care about the following warning: large size difference between variants
-    enum OneOrMore {
-        One(Result),
-        More(Vec<Result>),
+    // The elements are de-serialized straight from the input (rather than through a `Value` or an
+    // untagged enum), so their errors and the path to them (see `serde_path_to_error`) are kept
+    struct OneOrMore<T>(PhantomData<T>);
+
+    impl<'de, T: Deserialize<'de>> Visitor<'de> for OneOrMore<T> {
+        type Value = Vec<T>;
+
+        fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.write_str("a list, a single element or null")
+        }
+
+        fn visit_unit<E: de::Error>(self) -> std::result::Result<Self::Value, E> {
+            Ok(Vec::new())
+        }
+
+        fn visit_none<E: de::Error>(self) -> std::result::Result<Self::Value, E> {
+            Ok(Vec::new())
+        }
+
+        fn visit_seq<A: SeqAccess<'de>>(
+            self,
+            seq: A,
+        ) -> std::result::Result<Self::Value, A::Error> {
+            Vec::deserialize(SeqAccessDeserializer::new(seq))
+        }
+
+        fn visit_map<A: MapAccess<'de>>(
+            self,
+            map: A,
+        ) -> std::result::Result<Self::Value, A::Error> {
+            T::deserialize(MapAccessDeserializer::new(map)).map(|the_one| vec![the_one])
+        }
+
+        fn visit_str<E: de::Error>(self, v: &str) -> std::result::Result<Self::Value, E> {
+            T::deserialize(v.into_deserializer()).map(|the_one| vec![the_one])
+        }
+
+        fn visit_i64<E: de::Error>(self, v: i64) -> std::result::Result<Self::Value, E> {
+            T::deserialize(v.into_deserializer()).map(|the_one| vec![the_one])
+        }
+
+        fn visit_u64<E: de::Error>(self, v: u64) -> std::result::Result<Self::Value, E> {
+            T::deserialize(v.into_deserializer()).map(|the_one| vec![the_one])
+        }
+
+        fn visit_f64<E: de::Error>(self, v: f64) -> std::result::Result<Self::Value, E> {
+            T::deserialize(v.into_deserializer()).map(|the_one| vec![the_one])
+        }
+
+        fn visit_bool<E: de::Error>(self, v: bool) -> std::result::Result<Self::Value, E> {
+            T::deserialize(v.into_deserializer()).map(|the_one| vec![the_one])
+        }
     }
 
-    // Attempts de-serialization
-    let one_or_more = match serde_path_to_error::deserialize(deserializer) {
-        Ok(one_or_more_deserialized) => one_or_more_deserialized,
-        Err(err) => panic!("Full error path: {}", err.path()),
-    };
-
-    // If we got one object instead of a vector, wrap it in a vector
-    // Ok(match OneOrMore::deserialize(deserializer)? {
-    Ok(match one_or_more {
-        OneOrMore::One(the_one) => vec![the_one],
-        OneOrMore::More(the_more) => the_more,
-    })
-
-    // Ok(Vec::<Result>::deserialize(deserializer)?)
-}
-
-fn one_or_more_string<'de, D>(deserializer: D) -> std::result::Result<Vec<String>, D::Error>
-where
-    D: Deserializer<'de>,
-{
-    #[derive(Deserialize)]
-    #[serde(untagged)]
-    enum OneOrMore {
-        One(String),
-        More(Vec<String>),
-    }
-
-    Ok(match OneOrMore::deserialize(deserializer)? {
-        OneOrMore::One(the_one) => vec![the_one],
-        OneOrMore::More(the_more) => the_more,
-    })
+    deserializer.deserialize_any(OneOrMore(PhantomData))
 }
 
+/// An episode, as returned by the episode search
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Result {
+    /// The id of the episode
     pub id: String,
+    /// The organization hosting the episode
     pub org: String,
+    /// The tracks, catalogs and attachments of the episode
     pub mediapackage: Mediapackage,
+    /// The description of the episode
+    pub dc_description: Option<String>,
+    /// Usually names, but the API may mix in numbers
+    #[serde(default, deserialize_with = "one_or_more")]
+    pub dc_creator: Vec<Value>,
     // TODO uncomment the lines below
     // pub dc_extent: i64,
     // pub dc_title: String,
-    // pub dc_creator: Option<String>,
     // pub dc_publisher: Option<String>,
     // pub dc_created: String,
     // pub dc_spatial: String,
@@ -86,27 +128,43 @@ pub struct Result {
     // pub segments: Option<Segments>,
 }
 
+/// Everything published for an episode
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Mediapackage {
+    /// The duration in milliseconds
     pub duration: i64,
+    /// The id of the episode
     pub id: String,
+    /// When the lecture was recorded
     pub start: String,
+    /// The title of the episode
     pub title: String,
+    /// Empty for episodes which don't belong to a series
+    #[serde(default)]
     pub series: String,
+    /// The title of the series (empty if there's none)
+    #[serde(default)]
     pub seriestitle: String,
+    /// The video and audio tracks
     pub media: Media,
+    /// The metadata catalogs
     pub metadata: Metadata,
+    /// The attachments (e.g. preview images)
     pub attachments: Attachments,
+    /// Where the episode is published
     pub publications: String,
     // I got a response with [0, "text"] once instead of just "text"
     // TODO handle an array with both strings and numbers for some reason
     // pub creators: Option<Creators>,
 }
 
+/// The tracks of an episode
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Media {
+    /// The tracks, in all types and qualities
+    #[serde(default, deserialize_with = "one_or_more")]
     pub track: Vec<Track>,
 }
 
@@ -114,16 +172,23 @@ pub struct Media {
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Track {
+    /// The id of the track
     pub id: String,
+    /// Which view of the lecture the track shows
     #[serde(rename = "type")]
     pub type_field: TrackType,
+    /// A reference to the track, e.g. `track:<id>`
     #[serde(rename = "ref")]
     pub ref_field: String,
+    /// The mimetype (e.g. `video/mp4`)
     pub mimetype: String,
     /// Contains info on the videos quality
     pub tags: Tags,
+    /// Where the track is downloaded from
     pub url: String,
+    /// The checksum of the file (if the server advertises one)
     pub checksum: Option<Checksum>,
+    /// The duration in milliseconds
     pub duration: i64,
     // TODO uncomment the lines below
     // /// Should be `Some` for the things we care about
@@ -133,191 +198,315 @@ pub struct Track {
     // pub live: bool,
     /// Should be `None` for the things we care about
     pub transport: Option<String>,
-    // pub size: Option<i64>,
+    /// In bytes
+    pub size: Option<i64>,
     // pub master: Option<bool>,
 }
 
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Copy)]
+/// Which view of the lecture a track shows
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize, Copy)]
 pub enum TrackType {
+    /// The lecturer, with audio
     #[serde(rename = "presenter/delivery")]
     Presenter,
+    /// The lecturer, without audio
     #[serde(rename = "presenter_video/delivery")]
     PresenterNoAudio,
+    /// The slides or the screen
     #[serde(rename = "presentation/delivery")]
+    #[default]
     Presentation,
+    /// The unprocessed recording
     #[serde(rename = "raw/delivery")]
     Raw,
 }
 
-impl Default for TrackType {
-    fn default() -> Self {
-        TrackType::Presentation
-    }
-}
-
+/// The tags of a track, catalog or attachment
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Tags {
-    #[serde(deserialize_with = "one_or_more_string")]
+    /// The tags, e.g. `high-quality` or `engage-download`
+    #[serde(default, deserialize_with = "one_or_more")]
     pub tag: Vec<String>,
 }
 
+/// The checksum of a track
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Checksum {
+    /// The hash algorithm (e.g. `md5`)
     #[serde(rename = "type")]
     pub type_field: String,
+    /// The hex digest
     #[serde(rename = "$")]
     pub field: String,
 }
 
+/// The audio stream of a track
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Audio {
+    /// The id of the stream
     pub id: String,
+    /// The device it was recorded with
     pub device: String,
+    /// The encoder it was encoded with
     pub encoder: Encoder,
+    /// The number of frames
     pub framecount: i64,
+    /// The number of channels
     pub channels: i64,
+    /// The sampling rate in Hz
     pub samplingrate: i64,
+    /// The bitrate in bits per second
     pub bitrate: i64,
 }
 
+/// The encoder of a stream
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Encoder {
+    /// The name of the encoder
     #[serde(rename = "type")]
     pub type_field: String,
 }
 
+/// The video stream of a track
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Video {
+    /// The id of the stream
     pub id: String,
+    /// The device it was recorded with
     pub device: String,
+    /// The encoder it was encoded with
     pub encoder: Encoder,
+    /// The number of frames
     pub framecount: i64,
+    /// The bitrate in bits per second
     pub bitrate: f64,
+    /// The frames per second
     pub framerate: f64,
+    /// The resolution, e.g. `1920x1080`
     pub resolution: String,
 }
 
+/// The metadata catalogs of an episode
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Metadata {
+    /// The catalogs
+    #[serde(default, deserialize_with = "one_or_more")]
     pub catalog: Vec<Catalog>,
 }
 
+/// A metadata catalog (e.g. Dublin Core)
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Catalog {
+    /// The id of the catalog
     pub id: String,
+    /// The kind of catalog, e.g. `dublincore/episode`
     #[serde(rename = "type")]
     pub type_field: String,
+    /// The mimetype (e.g. `text/xml`)
     pub mimetype: String,
+    /// The tags of the catalog
     pub tags: Tags,
+    /// Where the catalog is downloaded from
     pub url: String,
+    /// The checksum of the file (if the server advertises one)
     pub checksum: Option<Checksum2>,
+    /// A reference to the catalog
     #[serde(rename = "ref")]
     pub ref_field: Option<String>,
 }
 
+/// The checksum of a catalog
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Checksum2 {
+    /// The hash algorithm (e.g. `md5`)
     #[serde(rename = "type")]
     pub type_field: String,
+    /// The hex digest
     #[serde(rename = "$")]
     pub field: String,
 }
 
+/// The attachments of an episode
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Attachments {
+    /// The attachments
+    #[serde(default, deserialize_with = "one_or_more")]
     pub attachment: Vec<Attachment>,
 }
 
+/// An attachment, e.g. a preview image
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Attachment {
+    /// The id of the attachment
     pub id: String,
+    /// The kind of attachment, e.g. `presenter/player+preview`
     #[serde(rename = "type")]
     pub type_field: String,
+    /// A reference to the attachment
     #[serde(rename = "ref")]
     pub ref_field: Option<String>,
+    /// The mimetype (e.g. `image/jpeg`)
     pub mimetype: String,
+    /// The tags of the attachment
     pub tags: Tags,
+    /// Where the attachment is downloaded from
     pub url: String,
+    /// The size in bytes
     pub size: Option<i64>,
+    /// Further properties of the attachment
     pub additional_properties: Option<AdditionalProperties>,
+    /// The checksum of the file (if the server advertises one)
     pub checksum: Option<Checksum3>,
 }
 
+/// The checksum of an attachment
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Checksum3 {
+    /// The hash algorithm (e.g. `md5`)
     #[serde(rename = "type")]
     pub type_field: String,
+    /// The hex digest
     #[serde(rename = "$")]
     pub field: String,
 }
 
+/// Further properties of an attachment
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct AdditionalProperties {
+    /// The properties
     pub property: Vec<Property>,
 }
 
+/// A property of an attachment
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Property {
+    /// The name of the property
     pub key: String,
+    /// The value of the property
     #[serde(rename = "$")]
     pub field: String,
 }
 
+/// The creators of an episode
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Creators {
+    /// The name of the creator
     pub creator: String,
 }
 
+/// The keywords of an episode
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Keywords {
-    #[serde(deserialize_with = "one_or_more_string")]
+    /// The keywords
+    #[serde(default, deserialize_with = "one_or_more")]
     pub keywords: Vec<String>,
 }
 
+/// The segments (i.e. slides) of an episode
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Segments {
+    /// The segments
     pub segment: Vec<Segment>,
 }
 
+/// A segment of an episode, usually a slide
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Segment {
+    /// The position of the segment
     pub index: i64,
+    /// When the segment starts (in milliseconds)
     pub time: i64,
+    /// The duration in milliseconds
     pub duration: i64,
+    /// How well the segment matches the search
     pub relevance: i64,
+    /// Whether the segment matches the search
     pub hit: bool,
+    /// The text recognized in the segment
     pub text: String,
+    /// The preview images of the segment
     pub previews: Previews,
 }
 
+/// The preview images of a segment
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Previews {
+    /// The preview image
     pub preview: Preview,
 }
 
+/// A preview image of a segment
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Preview {
+    /// A reference to the image
     #[serde(rename = "ref")]
     pub ref_field: String,
+    /// The URL of the image
     #[serde(rename = "$")]
     pub field: String,
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_one_or_more() {
+        let catalog = r#"{"id": "c", "type": "dublincore/episode", "mimetype": "text/xml", "tags": {"tag": "engage-download"}, "url": "https://example.org/c.xml"}"#;
+
+        let one: Metadata = serde_json::from_str(&format!(r#"{{"catalog": {catalog}}}"#)).unwrap();
+        assert_eq!(one.catalog.len(), 1);
+        assert_eq!(one.catalog[0].tags.tag, vec!["engage-download"]);
+
+        let more: Metadata =
+            serde_json::from_str(&format!(r#"{{"catalog": [{catalog}, {catalog}]}}"#)).unwrap();
+        assert_eq!(more.catalog.len(), 2);
+
+        let none: SearchResults = serde_json::from_str(
+            r#"{"offset": 0, "limit": 100, "total": 0, "searchTime": 1, "query": ""}"#,
+        )
+        .unwrap();
+        assert!(none.result.is_empty());
+
+        #[derive(Deserialize)]
+        struct Creators {
+            #[serde(deserialize_with = "one_or_more")]
+            creators: Vec<Value>,
+        }
+
+        let one: Creators = serde_json::from_str(r#"{"creators": "Jane Doe"}"#).unwrap();
+        assert_eq!(one.creators, ["Jane Doe"]);
+        let mixed: Creators = serde_json::from_str(r#"{"creators": [0, "Jane Doe"]}"#).unwrap();
+        assert_eq!(mixed.creators.len(), 2);
+
+        // Errors inside a list keep the path to the element
+        let text = format!(
+            r#"{{"catalog": [{catalog}, {}]}}"#,
+            catalog.replace(r#""url": "https://example.org/c.xml""#, r#""url": 42"#)
+        );
+        let err = serde_path_to_error::deserialize::<_, Metadata>(
+            &mut serde_json::Deserializer::from_str(&text),
+        )
+        .unwrap_err();
+        assert_eq!(err.path().to_string(), "catalog[1].url");
+    }
+}
@@ -1,2 +1,10 @@
+//! The (partial) schema of the responses of the Opencast API.
+
+// Not every part of the API schema is used (yet)
+/// The responses of `search/episode.json`
+#[allow(dead_code)]
 pub mod episodes;
+/// The response of `search/episode.json` to users who aren't logged in
 pub mod oof;
+/// The responses of `search/series.json`
+pub mod series;
@@ -1,18 +1,26 @@
 use serde::{Deserialize, Serialize};
 
+/// The response of an episode search to users who aren't logged in
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Root {
+    /// The number of episodes, but not the episodes themselves
     #[serde(rename = "search-results")]
     pub search_results: SearchResults,
 }
 
+/// A page of search results, without the results
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct SearchResults {
+    /// The number of results before this page
     pub offset: i64,
+    /// The maximum number of results on a page
     pub limit: i64,
+    /// The number of results on all pages
     pub total: i64,
+    /// How long the search took (in milliseconds)
     pub search_time: i64,
+    /// The search as the server understood it
     pub query: String,
 }
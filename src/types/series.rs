@@ -0,0 +1,57 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::episodes::one_or_more;
+
+/// The response of a series search
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SeriesData {
+    /// The (page of) series found
+    #[serde(rename = "search-results")]
+    pub search_results: SearchResults,
+}
+
+/// A page of search results
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchResults {
+    /// The number of results before this page
+    pub offset: i64,
+    /// The maximum number of results on a page
+    pub limit: i64,
+    /// The number of results on all pages
+    pub total: i64,
+    /// How long the search took (in milliseconds)
+    pub search_time: i64,
+    /// The search as the server understood it
+    pub query: String,
+    /// The series on this page
+    #[serde(default, deserialize_with = "one_or_more")]
+    pub result: Vec<Result>,
+}
+
+/// A series, as returned by the series search
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Result {
+    /// The id of the series
+    pub id: String,
+    /// The organization hosting the series
+    pub org: String,
+    /// The title of the series
+    #[serde(default)]
+    pub dc_title: String,
+    /// Usually names, but the API may mix in numbers (like for episodes)
+    #[serde(default, deserialize_with = "one_or_more")]
+    pub dc_creator: Vec<Value>,
+    /// Further lecturers (like `dc_creator`)
+    #[serde(default, deserialize_with = "one_or_more")]
+    pub dc_contributor: Vec<Value>,
+    /// When the series was created
+    pub dc_created: Option<String>,
+    /// When the series is held (e.g. the term)
+    pub dc_temporal: Option<String>,
+    /// The description of the series
+    pub dc_description: Option<String>,
+}
@@ -0,0 +1,413 @@
+//! An in-process stand-in for an Opencast server, serving the fixtures in `tests/fixtures`.
+
+#![allow(dead_code)]
+
+use hyper::header::{
+    CONTENT_LENGTH, CONTENT_RANGE, CONTENT_TYPE, COOKIE, LOCATION, RANGE, RETRY_AFTER, SET_COOKIE,
+};
+use hyper::http::request::Parts;
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Method, Request, Response, Server, StatusCode};
+use reqwest::Url;
+use serde_json::Value;
+
+use std::collections::{HashMap, HashSet};
+use std::convert::Infallible;
+use std::fs;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// The folder with the anonymised API responses
+pub fn fixtures_path() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures")
+}
+
+/// Reads a fixture, pointing its URLs at the given server
+pub fn fixture(name: &str, server: &Url) -> String {
+    fs::read_to_string(fixtures_path().join(name))
+        .unwrap()
+        .replace("{{server}}", server.as_str())
+}
+
+/// The content of a media file: its name, repeated (the fixtures' checksums are computed the same way)
+pub fn media(name: &str) -> Vec<u8> {
+    name.as_bytes().repeat(4096)
+}
+
+/// A request the server received
+#[derive(Debug, Clone)]
+pub struct Received {
+    pub path: String,
+    pub query: Option<String>,
+    pub range: Option<String>,
+    pub cookie: Option<String>,
+}
+
+/// A running mock server, which stops when the test's runtime shuts down
+pub struct MockServer {
+    pub url: Url,
+    state: Arc<State>,
+}
+
+#[derive(Default)]
+struct State {
+    received: Mutex<Vec<Received>>,
+    /// The search responses by series id
+    responses: Mutex<HashMap<String, Value>>,
+    /// The session token requests need to carry (any token is accepted if `None`)
+    token: Mutex<Option<String>>,
+    /// The username and password accepted by the form login
+    credentials: Mutex<Option<(String, String)>>,
+    /// The number of successful logins, numbering their session tokens
+    logins: Mutex<usize>,
+    /// The media files answered with a 503 for a while, by name: how many more times, and
+    /// the `Retry-After` (in seconds)
+    throttled: Mutex<HashMap<String, (usize, u64)>>,
+    /// The media files served with content not matching their checksums
+    corrupted: Mutex<HashSet<String>>,
+}
+
+impl MockServer {
+    /// Starts a server on a random local port, serving all fixtures
+    pub fn start() -> Self {
+        let state = Arc::new(State::default());
+
+        let make_service = {
+            let state = Arc::clone(&state);
+
+            make_service_fn(move |_| {
+                let state = Arc::clone(&state);
+
+                async move {
+                    Ok::<_, Infallible>(service_fn(move |request: Request<Body>| {
+                        let state = Arc::clone(&state);
+
+                        async move {
+                            let (parts, body) = request.into_parts();
+                            let body = hyper::body::to_bytes(body).await.unwrap_or_default();
+                            Ok::<_, Infallible>(handle(&parts, &body, &state))
+                        }
+                    }))
+                }
+            })
+        };
+
+        let server = Server::bind(&SocketAddr::from(([127, 0, 0, 1], 0))).serve(make_service);
+        let url = Url::parse(&format!("http://{}/", server.local_addr())).unwrap();
+        tokio::spawn(server);
+
+        state.responses.lock().unwrap().extend(load_fixtures(&url));
+
+        MockServer { url, state }
+    }
+
+    /// Only accepts requests with the given session token from now on, as if the old one expired.
+    ///
+    /// Other requests get results without any episodes, or are redirected to the login page.
+    pub fn require_token(&self, token: &str) {
+        *self.state.token.lock().unwrap() = Some(token.to_owned());
+    }
+
+    /// Only accepts requests with a session token handed out by logging in with these credentials
+    pub fn require_login(&self, username: &str, password: &str) {
+        *self.state.credentials.lock().unwrap() = Some((username.to_owned(), password.to_owned()));
+        self.require_token("not-logged-in");
+    }
+
+    /// Answers the next `times` requests for a media file with a 503, asking to retry after
+    /// `retry_after` seconds
+    pub fn throttle(&self, name: &str, times: usize, retry_after: u64) {
+        self.state
+            .throttled
+            .lock()
+            .unwrap()
+            .insert(name.to_owned(), (times, retry_after));
+    }
+
+    /// Serves a media file with a byte flipped from now on, so it won't match its checksum
+    pub fn corrupt(&self, name: &str) {
+        self.state.corrupted.lock().unwrap().insert(name.to_owned());
+    }
+
+    /// The number of successful logins so far
+    pub fn logins(&self) -> usize {
+        *self.state.logins.lock().unwrap()
+    }
+
+    /// The requests received so far
+    pub fn received(&self) -> Vec<Received> {
+        self.state.received.lock().unwrap().clone()
+    }
+}
+
+/// Maps the series ids of the fixtures to their parsed responses
+fn load_fixtures(server: &Url) -> HashMap<String, Value> {
+    fs::read_dir(fixtures_path())
+        .unwrap()
+        .map(|entry| {
+            let name = entry.unwrap().file_name().to_string_lossy().into_owned();
+            let value: Value = serde_json::from_str(&fixture(&name, server)).unwrap();
+            let query = value["search-results"]["query"].as_str().unwrap();
+
+            (query.trim_start_matches("sid:").to_owned(), value)
+        })
+        .collect()
+}
+
+fn handle(request: &Parts, body: &[u8], state: &State) -> Response<Body> {
+    let header = |name| {
+        request
+            .headers
+            .get(name)
+            .and_then(|value: &hyper::header::HeaderValue| value.to_str().ok())
+            .map(str::to_owned)
+    };
+
+    state.received.lock().unwrap().push(Received {
+        path: request.uri.path().to_owned(),
+        query: request.uri.query().map(str::to_owned),
+        range: header(RANGE),
+        cookie: header(COOKIE),
+    });
+
+    let path = request.uri.path();
+    let logged_in = match &*state.token.lock().unwrap() {
+        Some(token) => {
+            header(COOKIE).is_some_and(|cookie| cookie.contains(&format!("JSESSIONID={token}")))
+        }
+        None => true,
+    };
+
+    if path == "/j_spring_security_check" && request.method == Method::POST {
+        login(body, state)
+    } else if path == "/" || path == "/login.html" {
+        Response::builder()
+            .header(CONTENT_TYPE, "text/html; charset=utf-8")
+            .body(Body::from("<html><body><form>Login</form></body></html>"))
+            .unwrap()
+    } else if path == "/search/episode.json" {
+        let query = request.uri.query().unwrap_or_default();
+
+        match search(query, &state.responses.lock().unwrap()) {
+            Ok(mut response) => {
+                // Anonymous users are told how many episodes there are, but not which
+                if !logged_in {
+                    if let Some(search_results) = response["search-results"].as_object_mut() {
+                        search_results.remove("result");
+                    }
+                }
+
+                Response::builder()
+                    .header(CONTENT_TYPE, "application/json")
+                    .body(Body::from(response.to_string()))
+                    .unwrap()
+            }
+            Err(code) => status(code),
+        }
+    } else if path == "/info/me.json" {
+        let roles = match logged_in {
+            true => vec!["ROLE_USER", "ROLE_ANONYMOUS"],
+            false => vec!["ROLE_ANONYMOUS"],
+        };
+
+        Response::builder()
+            .header(CONTENT_TYPE, "application/json")
+            .body(Body::from(
+                serde_json::json!({ "roles": roles }).to_string(),
+            ))
+            .unwrap()
+    } else if let Some(name) = path.strip_prefix("/static/") {
+        let throttled = match state.throttled.lock().unwrap().get_mut(name) {
+            Some((times, retry_after)) if *times > 0 => {
+                *times -= 1;
+                Some(*retry_after)
+            }
+            _ => None,
+        };
+
+        if let Some(retry_after) = throttled {
+            Response::builder()
+                .status(StatusCode::SERVICE_UNAVAILABLE)
+                .header(RETRY_AFTER, retry_after)
+                .body(Body::empty())
+                .unwrap()
+        } else if name.starts_with("forbidden") {
+            // Even users who are logged in may lack access to some videos
+            status(StatusCode::FORBIDDEN)
+        } else if logged_in {
+            let mut content = media(name);
+            if state.corrupted.lock().unwrap().contains(name) {
+                content[0] ^= 0xff;
+            }
+
+            serve_media(content, header(RANGE).as_deref())
+        } else {
+            Response::builder()
+                .status(StatusCode::FOUND)
+                .header(LOCATION, "/login.html")
+                .body(Body::empty())
+                .unwrap()
+        }
+    } else {
+        status(StatusCode::NOT_FOUND)
+    }
+}
+
+/// Checks the credentials of a form login, handing out a new session token if they match
+fn login(body: &[u8], state: &State) -> Response<Body> {
+    let form = Url::parse(&format!(
+        "http://localhost/?{}",
+        String::from_utf8_lossy(body)
+    ))
+    .unwrap()
+    .query_pairs()
+    .into_owned()
+    .collect::<HashMap<_, _>>();
+
+    let accepted =
+        state
+            .credentials
+            .lock()
+            .unwrap()
+            .as_ref()
+            .is_some_and(|(username, password)| {
+                form.get("j_username") == Some(username) && form.get("j_password") == Some(password)
+            });
+
+    if !accepted {
+        return Response::builder()
+            .status(StatusCode::FOUND)
+            .header(LOCATION, "/login.html?error")
+            .body(Body::empty())
+            .unwrap();
+    }
+
+    let mut logins = state.logins.lock().unwrap();
+    *logins += 1;
+    let token = format!("session-{logins}");
+    *state.token.lock().unwrap() = Some(token.to_owned());
+
+    Response::builder()
+        .status(StatusCode::FOUND)
+        .header(LOCATION, "/")
+        .header(SET_COOKIE, format!("JSESSIONID={token}; Path=/; HttpOnly"))
+        .body(Body::empty())
+        .unwrap()
+}
+
+/// Answers an episode search by series (`sid`) or episode (`id`), honouring `offset` and `limit`
+fn search(query: &str, responses: &HashMap<String, Value>) -> Result<Value, StatusCode> {
+    let params = Url::parse(&format!("http://localhost/?{query}"))
+        .unwrap()
+        .query_pairs()
+        .into_owned()
+        .collect::<HashMap<_, _>>();
+    let number = |name: &str, default| {
+        params
+            .get(name)
+            .and_then(|value| value.parse().ok())
+            .unwrap_or(default)
+    };
+
+    let mut response = if let Some(series) = params.get("sid") {
+        match responses.get(series) {
+            Some(response) => response.clone(),
+            None => return Err(StatusCode::NOT_FOUND),
+        }
+    } else if let Some(episode) = params.get("id") {
+        let found = responses.values().find_map(|response| {
+            results(response)
+                .into_iter()
+                .find(|result| result["id"] == episode.as_str())
+                .map(|result| (response, result))
+        });
+
+        match found {
+            Some((response, result)) => {
+                let mut response = response.clone();
+                response["search-results"]["total"] = 1.into();
+                response["search-results"]["result"] = result;
+                response
+            }
+            None => return Err(StatusCode::NOT_FOUND),
+        }
+    } else {
+        return Err(StatusCode::BAD_REQUEST);
+    };
+
+    // Like Opencast, return a single result as an object instead of an array
+    let page = results(&response)
+        .into_iter()
+        .skip(number("offset", 0))
+        .take(number("limit", usize::MAX))
+        .collect::<Vec<_>>();
+
+    let search_results = &mut response["search-results"];
+    search_results["offset"] = number("offset", 0).into();
+    match page.len() {
+        0 => {
+            if let Some(object) = search_results.as_object_mut() {
+                object.remove("result");
+            }
+        }
+        1 => search_results["result"] = page[0].clone(),
+        _ => search_results["result"] = Value::Array(page),
+    }
+
+    Ok(response)
+}
+
+/// The results of a response, whether there are none, one (as an object) or several
+fn results(response: &Value) -> Vec<Value> {
+    match &response["search-results"]["result"] {
+        Value::Null => Vec::new(),
+        Value::Array(results) => results.clone(),
+        result => vec![result.clone()],
+    }
+}
+
+/// Serves a media file, supporting `bytes=<start>-` range requests
+fn serve_media(content: Vec<u8>, range: Option<&str>) -> Response<Body> {
+    let total = content.len();
+
+    let start = match range.and_then(|range| range.strip_prefix("bytes=")) {
+        Some(range) => match range.trim_end_matches('-').parse::<usize>() {
+            Ok(start) => start,
+            Err(_) => return status(StatusCode::BAD_REQUEST),
+        },
+        None => {
+            return Response::builder()
+                .header(CONTENT_TYPE, "video/mp4")
+                .header(CONTENT_LENGTH, total)
+                .body(Body::from(content))
+                .unwrap()
+        }
+    };
+
+    if start >= total {
+        return Response::builder()
+            .status(StatusCode::RANGE_NOT_SATISFIABLE)
+            .header(CONTENT_RANGE, format!("bytes */{total}"))
+            .body(Body::empty())
+            .unwrap();
+    }
+
+    Response::builder()
+        .status(StatusCode::PARTIAL_CONTENT)
+        .header(CONTENT_TYPE, "video/mp4")
+        .header(CONTENT_LENGTH, total - start)
+        .header(
+            CONTENT_RANGE,
+            format!("bytes {}-{}/{}", start, total - 1, total),
+        )
+        .body(Body::from(content[start..].to_vec()))
+        .unwrap()
+}
+
+fn status(status: StatusCode) -> Response<Body> {
+    Response::builder()
+        .status(status)
+        .body(Body::empty())
+        .unwrap()
+}
@@ -0,0 +1,822 @@
+//! Crawls and downloads the fixtures from a mock server, without touching the network.
+
+mod common;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use tube_get::constants;
+use tube_get::download::{self, DownloadOptions, Silent};
+use tube_get::error::{self, Error};
+use tube_get::extractor::{self, Course, Quality, TrackSelection};
+use tube_get::retry::RetryPolicy;
+use tube_get::sanitize::SanitizeMode;
+use tube_get::session::{self, Credentials, Renew, RenewFuture, Session, SessionBuilder};
+use tube_get::state::{StateStore, TrackState};
+use tube_get::target::Target;
+use tube_get::template::{self, Template};
+use tube_get::types::episodes::TrackType;
+
+use common::MockServer;
+
+const SINGLE: &str = "11111111-1111-4111-8111-111111111111";
+const MULTI: &str = "22222222-2222-4222-8222-222222222222";
+const NO_CHECKSUMS: &str = "33333333-3333-4333-8333-333333333333";
+const FLAVORS: &str = "44444444-4444-4444-8444-444444444444";
+const LOGGED_OUT: &str = "55555555-5555-4555-8555-555555555555";
+const UNKNOWN: &str = "77777777-7777-4777-8777-777777777777";
+const DUPLICATES: &str = "66666666-6666-4666-8666-666666666666";
+const DUPLICATES_FIRST: &str = "f6666666-0000-4000-8000-000000000001";
+const DUPLICATES_SECOND: &str = "f6666666-0000-4000-8000-000000000002";
+
+const RETRY_POLICY: RetryPolicy = RetryPolicy { max_attempts: 1 };
+
+fn selection(quality: Quality, track_types: Option<Vec<TrackType>>) -> TrackSelection {
+    TrackSelection {
+        quality,
+        track_types,
+        mimetype: constants::MP4_MIME.to_owned(),
+    }
+}
+
+/// Downloads to the given folder, which the courses don't share with anything else
+fn download_options(destination: &Path, jobs: usize) -> DownloadOptions {
+    DownloadOptions {
+        destination: destination.to_owned(),
+        jobs,
+        retry_policy: RETRY_POLICY,
+        verbosity: 0,
+        exclusive_folders: true,
+    }
+}
+
+/// A fresh folder to download to
+fn temp_folder(name: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("tube-get-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&path);
+    fs::create_dir_all(&path).unwrap();
+    path
+}
+
+async fn crawl(
+    server: &MockServer,
+    session: &Session,
+    target: Target,
+    selection: &TrackSelection,
+) -> anyhow::Result<Course> {
+    let data = extractor::get_episodes(
+        session,
+        &server.url,
+        target,
+        constants::PAGE_SIZE,
+        RETRY_POLICY,
+        0,
+    )
+    .await?;
+    let mut course = extractor::extract_course_data(&data, selection)?;
+
+    let template = constants::DEFAULT_TEMPLATE.parse::<Template>().unwrap();
+    template::assign_paths(&mut course, &template, SanitizeMode::Posix);
+    extractor::select_episodes(&mut course, target, 0, None);
+
+    Ok(course)
+}
+
+fn session(server: &MockServer) -> Session {
+    SessionBuilder::new(server.url.clone())
+        .token("test-session")
+        .build()
+        .unwrap()
+}
+
+/// Renews the session with the next of a list of tokens, failing once they are used up
+struct Tokens(Mutex<Vec<&'static str>>);
+
+impl Renew for Tokens {
+    fn renew<'a>(&'a self, session: &'a Session) -> RenewFuture<'a> {
+        Box::pin(async move {
+            let mut tokens = self.0.lock().unwrap();
+            if tokens.is_empty() {
+                return Err(Error::AuthExpired.into());
+            }
+
+            session.set_token(tokens.remove(0));
+            Ok(())
+        })
+    }
+}
+
+#[tokio::test]
+async fn test_single_result() {
+    let server = MockServer::start();
+    let target = Target::Series(SINGLE.parse().unwrap());
+
+    let course = crawl(
+        &server,
+        &session(&server),
+        target,
+        &selection(Quality::High, None),
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(course.title, "Example Lecture 101");
+    assert_eq!(course.id, SINGLE);
+    assert!(!course.partial);
+    assert_eq!(course.videos.len(), 1);
+
+    let video = &course.videos[0];
+    assert_eq!(video.title, "Introduction");
+    assert_eq!(video.presenters, ["Jane Doe"]);
+    assert_eq!(video.quality.as_deref(), Some("high"));
+    assert!(video.checksum.is_some());
+    assert_eq!(
+        video.path,
+        PathBuf::from("Example Lecture 101/Introduction_presenter.mp4")
+    );
+
+    // The session token is sent along with every request
+    let received = server.received();
+    assert_eq!(received.len(), 1);
+    assert_eq!(
+        received[0].cookie.as_deref(),
+        Some("JSESSIONID=test-session")
+    );
+    assert!(received[0]
+        .query
+        .as_deref()
+        .is_some_and(|query| query.contains(&format!("sid={SINGLE}"))));
+}
+
+#[tokio::test]
+async fn test_multiple_results() {
+    let server = MockServer::start();
+    let session = session(&server);
+    let target = Target::Series(MULTI.parse().unwrap());
+
+    let course = crawl(&server, &session, target, &selection(Quality::Medium, None))
+        .await
+        .unwrap();
+
+    assert_eq!(course.title, "Example Analysis");
+    assert_eq!(course.videos.len(), 6);
+    assert!(course
+        .videos
+        .iter()
+        .all(|video| video.quality.as_deref() == Some("medium")));
+
+    // The episodes are numbered in the order they were recorded, not in the order of the API
+    let indices = course
+        .videos
+        .iter()
+        .map(|video| (video.title.as_str(), video.index))
+        .collect::<Vec<_>>();
+    assert!(indices.contains(&("Lecture 1: Numbers", 1)));
+    assert!(indices.contains(&("Lecture 2: Limits", 2)));
+    assert!(indices.contains(&("Lecture 3: Series", 3)));
+
+    // Creators which aren't strings are skipped
+    let first = course.videos.iter().find(|video| video.index == 1).unwrap();
+    assert_eq!(first.presenters, ["Jane Doe"]);
+
+    // A single episode of the series
+    let target = Target::Episode("b2222222-0000-4000-8000-000000000002".parse().unwrap());
+    let course = crawl(
+        &server,
+        &session,
+        target,
+        &selection(Quality::High, Some(vec![TrackType::Presentation])),
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(course.id, MULTI);
+    assert!(course.partial);
+    assert_eq!(course.videos.len(), 1);
+    assert_eq!(course.videos[0].title, "Lecture 2: Limits");
+    assert_eq!(course.videos[0].video_type, TrackType::Presentation);
+
+    // ... is numbered within the series
+    assert_eq!(course.videos[0].index, 2);
+}
+
+#[tokio::test]
+async fn test_paging() {
+    let server = MockServer::start();
+    let session = session(&server);
+    let target = Target::Series(MULTI.parse().unwrap());
+
+    // The three episodes take two pages
+    let data = extractor::get_episodes(&session, &server.url, target, 2, RETRY_POLICY, 0)
+        .await
+        .unwrap();
+    assert_eq!(data.search_results.result.len(), 3);
+    assert_eq!(data.search_results.total, 3);
+
+    let pages = server
+        .received()
+        .into_iter()
+        .filter_map(|request| request.query)
+        .collect::<Vec<_>>();
+    assert_eq!(pages.len(), 2);
+    assert!(pages[0].contains("limit=2&offset=0"));
+    assert!(pages[1].contains("limit=2&offset=2"));
+
+    // Skipping and limiting applies to the merged episodes, in the order they were recorded,
+    // without renumbering them
+    let selection = selection(Quality::High, Some(vec![TrackType::Presenter]));
+    let mut course = extractor::extract_course_data(&data, &selection).unwrap();
+    let template = "{index:02} {title}.{ext}".parse::<Template>().unwrap();
+    template::assign_paths(&mut course, &template, SanitizeMode::Posix);
+
+    let select = |skip, limit| {
+        let mut course = course.clone();
+        extractor::select_episodes(&mut course, target, skip, limit);
+
+        let mut paths = course
+            .videos
+            .iter()
+            .map(|video| video.path.to_string_lossy().into_owned())
+            .collect::<Vec<_>>();
+        paths.sort();
+        (paths, course.partial)
+    };
+
+    assert_eq!(
+        select(1, Some(1)),
+        (vec!["02 Lecture 2: Limits.mp4".to_owned()], true)
+    );
+    assert_eq!(
+        select(1, None),
+        (
+            vec![
+                "02 Lecture 2: Limits.mp4".to_owned(),
+                "03 Lecture 3: Series.mp4".to_owned()
+            ],
+            true
+        )
+    );
+    assert_eq!(select(0, Some(3)).0.len(), 3);
+    assert!(!select(0, Some(3)).1);
+}
+
+#[tokio::test]
+async fn test_flavors() {
+    let server = MockServer::start();
+    let session = session(&server);
+    let target = Target::Series(FLAVORS.parse().unwrap());
+
+    let course = crawl(&server, &session, target, &selection(Quality::High, None))
+        .await
+        .unwrap();
+
+    let mut selected = course
+        .videos
+        .iter()
+        .map(|video| video.track_id.as_str())
+        .collect::<Vec<_>>();
+    selected.sort_unstable();
+
+    // The WebM track is skipped, and the medium presenter track replaces the missing high one
+    assert_eq!(
+        selected,
+        [
+            "t-d1-presentation-high",
+            "t-d1-presenter-medium",
+            "t-d1-presenter-video-high",
+            "t-d1-raw",
+        ]
+    );
+
+    let course = crawl(
+        &server,
+        &session,
+        target,
+        &selection(Quality::Smallest, Some(vec![TrackType::Presenter])),
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(course.videos.len(), 1);
+    assert_eq!(course.videos[0].track_id, "t-d1-presenter-low");
+}
+
+#[tokio::test]
+async fn test_logged_out() {
+    let server = MockServer::start();
+    let target = Target::Series(LOGGED_OUT.parse().unwrap());
+
+    let err = crawl(
+        &server,
+        &session(&server),
+        target,
+        &selection(Quality::High, None),
+    )
+    .await
+    .unwrap_err();
+
+    // The results are withheld although there are some
+    assert!(session::is_auth_expired(&err));
+    assert_eq!(error::exit_code(&err), error::EXIT_AUTH);
+}
+
+#[tokio::test]
+async fn test_renew_session() {
+    let server = MockServer::start();
+    let destination = temp_folder("renew");
+    let target = Target::Series(MULTI.parse().unwrap());
+    let tokens = Tokens(Mutex::new(vec!["first", "second"]));
+    let session = SessionBuilder::new(server.url.clone())
+        .token("expired")
+        .renewer(tokens)
+        .build()
+        .unwrap();
+
+    // The API withholds the results until the session was renewed
+    server.require_token("first");
+    let course = crawl(
+        &server,
+        &session,
+        target,
+        &selection(Quality::High, Some(vec![TrackType::Presenter])),
+    )
+    .await
+    .unwrap();
+    assert_eq!(course.videos.len(), 3);
+
+    // The session expires before the download: the parallel downloads only renew it once
+    server.require_token("second");
+    let options = download_options(&destination, 3);
+    download::download_course(options, course.clone(), None, Arc::new(Silent), &session)
+        .await
+        .unwrap();
+
+    // The login page wasn't saved as a video
+    for video in &course.videos {
+        let name = video.url.rsplit('/').next().unwrap();
+        let path = download::video_path(&destination, video);
+        assert_eq!(fs::read(&path).unwrap(), common::media(name));
+    }
+
+    // Once the session can't be renewed anymore, the downloads fail
+    server.require_token("third");
+    fs::remove_dir_all(&destination).unwrap();
+    let options = download_options(&destination, 1);
+    let err = download::download_course(options, course, None, Arc::new(Silent), &session)
+        .await
+        .unwrap_err();
+    assert_eq!(error::exit_code(&err), error::EXIT_AUTH);
+    assert!(err
+        .chain()
+        .any(|cause| matches!(cause.downcast_ref(), Some(Error::AuthExpired))));
+
+    fs::remove_dir_all(&destination).unwrap();
+}
+
+#[tokio::test]
+async fn test_download() {
+    let server = MockServer::start();
+    let session = session(&server);
+    let destination = temp_folder("download");
+    let target = Target::Series(MULTI.parse().unwrap());
+
+    let course = crawl(
+        &server,
+        &session,
+        target,
+        &selection(Quality::Low, Some(vec![TrackType::Presenter])),
+    )
+    .await
+    .unwrap();
+    assert_eq!(course.videos.len(), 3);
+
+    // Pretend a previous run was interrupted halfway through the first lecture
+    let resumed = course.videos.iter().find(|video| video.index == 1).unwrap();
+    let resumed_path = download::video_path(&destination, resumed);
+    let content = common::media("b1-presenter-low.mp4");
+    let mut part_path = resumed_path.clone().into_os_string();
+    part_path.push(".part");
+    fs::create_dir_all(resumed_path.parent().unwrap()).unwrap();
+    fs::write(&part_path, &content[..content.len() / 2]).unwrap();
+
+    let options = download_options(&destination, 2);
+    let state_path = destination.join("state.json");
+    let state_store = StateStore::load(&state_path).unwrap();
+
+    download::download_course(
+        options.clone(),
+        course.clone(),
+        Some(state_store),
+        Arc::new(Silent),
+        &session,
+    )
+    .await
+    .unwrap();
+
+    // All files match the checksums of the fixture (or the download would have failed)
+    for video in &course.videos {
+        let path = download::video_path(&destination, video);
+        let name = video.url.rsplit('/').next().unwrap();
+        assert_eq!(fs::read(&path).unwrap(), common::media(name));
+    }
+    assert!(!PathBuf::from(&part_path).exists());
+
+    let ranges = server
+        .received()
+        .into_iter()
+        .filter_map(|request| request.range)
+        .collect::<Vec<_>>();
+    assert_eq!(ranges, [format!("bytes={}-", content.len() / 2)]);
+
+    let state_store = StateStore::load(&state_path).unwrap();
+    for video in &course.videos {
+        assert!(matches!(
+            state_store.get(video),
+            Some(TrackState::Completed { .. })
+        ));
+    }
+
+    // A second run skips the downloaded files
+    let requests = server.received().len();
+    download::download_course(
+        options.clone(),
+        course.clone(),
+        None,
+        Arc::new(Silent),
+        &session,
+    )
+    .await
+    .unwrap();
+    assert_eq!(server.received().len(), requests);
+
+    // ... and so does one with the state store, even once the files were moved
+    for video in &course.videos {
+        fs::remove_file(download::video_path(&destination, video)).unwrap();
+    }
+    let state_store = StateStore::load(&state_path).unwrap();
+    download::download_course(
+        options,
+        course,
+        Some(state_store),
+        Arc::new(Silent),
+        &session,
+    )
+    .await
+    .unwrap();
+    assert_eq!(server.received().len(), requests);
+
+    fs::remove_dir_all(&destination).unwrap();
+}
+
+#[tokio::test]
+async fn test_duplicate_titles() {
+    let server = MockServer::start();
+    let session = session(&server);
+    let destination = temp_folder("duplicates");
+    let selection = selection(Quality::High, None);
+    let options = download_options(&destination, 2);
+    let state_path = destination.join("state.json");
+
+    // The second episode is named the same, whether it is downloaded on its own ...
+    let second = crawl(
+        &server,
+        &session,
+        Target::Episode(DUPLICATES_SECOND.parse().unwrap()),
+        &selection,
+    )
+    .await
+    .unwrap();
+    assert_eq!(second.videos.len(), 1);
+
+    let series = Target::Series(DUPLICATES.parse().unwrap());
+    let course = crawl(&server, &session, series, &selection).await.unwrap();
+    assert_eq!(course.videos.len(), 2);
+
+    // ... or with the whole series
+    let path = |id: &str| {
+        let video = course.videos.iter().find(|video| video.id == id).unwrap();
+        download::video_path(&destination, video)
+    };
+    assert_eq!(
+        download::video_path(&destination, &second.videos[0]),
+        path(DUPLICATES_SECOND)
+    );
+    assert_ne!(path(DUPLICATES_FIRST), path(DUPLICATES_SECOND));
+    assert!(path(DUPLICATES_SECOND)
+        .to_string_lossy()
+        .ends_with("Uebung_presenter (2).mp4"));
+
+    download::download_course(
+        options.clone(),
+        second,
+        Some(StateStore::load(&state_path).unwrap()),
+        Arc::new(Silent),
+        &session,
+    )
+    .await
+    .unwrap();
+
+    // So the first episode isn't mistaken for the second one
+    download::download_course(
+        options.clone(),
+        course.clone(),
+        Some(StateStore::load(&state_path).unwrap()),
+        Arc::new(Silent),
+        &session,
+    )
+    .await
+    .unwrap();
+    assert_eq!(
+        fs::read(path(DUPLICATES_FIRST)).unwrap(),
+        common::media("f1-presenter-high.mp4")
+    );
+    assert_eq!(
+        fs::read(path(DUPLICATES_SECOND)).unwrap(),
+        common::media("f2-presenter-high.mp4")
+    );
+    let media_requests = |server: &MockServer| {
+        server
+            .received()
+            .into_iter()
+            .filter(|request| request.path.starts_with("/static/"))
+            .count()
+    };
+    assert_eq!(media_requests(&server), 2);
+
+    // A file of another episode (e.g. from an older version) is neither overwritten
+    // nor recorded as downloaded
+    fs::remove_dir_all(&destination).unwrap();
+    fs::create_dir_all(path(DUPLICATES_FIRST).parent().unwrap()).unwrap();
+    fs::write(
+        path(DUPLICATES_FIRST),
+        common::media("f2-presenter-high.mp4"),
+    )
+    .unwrap();
+
+    let result = download::download_course(
+        options,
+        course.clone(),
+        Some(StateStore::load(&state_path).unwrap()),
+        Arc::new(Silent),
+        &session,
+    )
+    .await;
+    assert!(result.is_err());
+    assert_eq!(
+        fs::read(path(DUPLICATES_FIRST)).unwrap(),
+        common::media("f2-presenter-high.mp4")
+    );
+
+    let state_store = StateStore::load(&state_path).unwrap();
+    let states = course
+        .videos
+        .iter()
+        .map(|video| (video.id.as_str(), state_store.get(video).cloned()))
+        .collect::<Vec<_>>();
+    assert!(states.contains(&(DUPLICATES_FIRST, None)));
+    assert!(matches!(
+        states.iter().find(|(id, _)| *id == DUPLICATES_SECOND),
+        Some((_, Some(TrackState::Completed { .. })))
+    ));
+
+    fs::remove_dir_all(&destination).unwrap();
+}
+
+#[tokio::test]
+async fn test_multiple_targets() {
+    let server = MockServer::start();
+    let destination = temp_folder("targets");
+    let config_path = destination.join("config.toml");
+    fs::write(&config_path, "").unwrap();
+
+    // An unknown series doesn't keep the others from being downloaded
+    let output = tokio::process::Command::new(env!("CARGO_BIN_EXE_tube-get"))
+        .env_clear()
+        .arg("--server")
+        .arg(server.url.as_str())
+        .arg("--config")
+        .arg(&config_path)
+        .arg("--destination")
+        .arg(&destination)
+        .args(["token", UNKNOWN, SINGLE])
+        .stdin(std::process::Stdio::null())
+        .output()
+        .await
+        .unwrap();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+
+    let downloaded = destination.join("Example Lecture 101/Introduction_presenter.mp4");
+    assert_eq!(
+        fs::read(downloaded).unwrap(),
+        common::media("a1-presenter-high.mp4")
+    );
+
+    // ... but is reported in the summary, and in the exit code
+    assert!(stderr.contains(&format!("  series {}: failed: Nothing found at", UNKNOWN)));
+    assert!(stderr.contains(&format!("  Example Lecture 101 (series {}): ok", SINGLE)));
+    assert!(stderr.contains("1 of 2 downloads failed"));
+    assert_eq!(output.status.code(), Some(error::EXIT_NOT_FOUND.into()));
+
+    fs::remove_dir_all(&destination).unwrap();
+}
+
+#[tokio::test]
+async fn test_checksum_mismatch() {
+    let server = MockServer::start();
+    let session = session(&server);
+    let destination = temp_folder("mismatch");
+    let target = Target::Series(SINGLE.parse().unwrap());
+
+    let course = crawl(&server, &session, target, &selection(Quality::High, None))
+        .await
+        .unwrap();
+    server.corrupt("a1-presenter-high.mp4");
+
+    let err = download::download_course(
+        download_options(&destination, 1),
+        course.clone(),
+        None,
+        Arc::new(Silent),
+        &session,
+    )
+    .await
+    .unwrap_err();
+    assert_eq!(error::exit_code(&err), error::EXIT_FAILURE);
+
+    // Downloaded once, and again for every retry
+    let media_requests = server
+        .received()
+        .into_iter()
+        .filter(|request| request.path.starts_with("/static/"))
+        .count();
+    assert_eq!(media_requests, 1 + constants::CHECKSUM_RETRIES as usize);
+
+    // The corrupt download is kept aside, but never under the final name
+    let path = download::video_path(&destination, &course.videos[0]);
+    let mut corrupt_path = path.clone().into_os_string();
+    corrupt_path.push(".corrupt");
+    let mut corrupt = common::media("a1-presenter-high.mp4");
+    corrupt[0] ^= 0xff;
+    assert_eq!(fs::read(&corrupt_path).unwrap(), corrupt);
+    assert!(!path.exists());
+
+    fs::remove_dir_all(&destination).unwrap();
+}
+
+#[tokio::test]
+async fn test_retry_after() {
+    let server = MockServer::start();
+    let session = session(&server);
+    let destination = temp_folder("retry-after");
+    let target = Target::Series(SINGLE.parse().unwrap());
+
+    let course = crawl(&server, &session, target, &selection(Quality::High, None))
+        .await
+        .unwrap();
+
+    // Longer than the backoff before the first retry (at most a second)
+    server.throttle("a1-presenter-high.mp4", 1, 2);
+
+    let options = DownloadOptions {
+        retry_policy: RetryPolicy { max_attempts: 2 },
+        ..download_options(&destination, 1)
+    };
+    let started = Instant::now();
+    download::download_course(options, course, None, Arc::new(Silent), &session)
+        .await
+        .unwrap();
+    assert!(started.elapsed() >= Duration::from_secs(2));
+
+    let media_requests = server
+        .received()
+        .into_iter()
+        .filter(|request| request.path.starts_with("/static/"))
+        .count();
+    assert_eq!(media_requests, 2);
+
+    fs::remove_dir_all(&destination).unwrap();
+}
+
+#[tokio::test]
+async fn test_forbidden() {
+    let server = MockServer::start();
+    let session = session(&server);
+    let destination = temp_folder("forbidden");
+    let target = Target::Series(SINGLE.parse().unwrap());
+
+    let mut course = crawl(&server, &session, target, &selection(Quality::High, None))
+        .await
+        .unwrap();
+    course.videos[0].url = server.url.join("static/forbidden.mp4").unwrap().to_string();
+
+    let options = download_options(&destination, 1);
+
+    // Still logged in, so the video is just off limits
+    let err = download::download_course(
+        options.clone(),
+        course.clone(),
+        None,
+        Arc::new(Silent),
+        &session,
+    )
+    .await
+    .unwrap_err();
+    assert!(!session::is_auth_expired(&err));
+
+    // Once the session expired, the 403 is due to that
+    server.require_token("new-session");
+    let err = download::download_course(options, course, None, Arc::new(Silent), &session)
+        .await
+        .unwrap_err();
+    assert!(session::is_auth_expired(&err));
+
+    let checks = server
+        .received()
+        .into_iter()
+        .filter(|request| request.path == "/info/me.json")
+        .count();
+    assert_eq!(checks, 2);
+
+    fs::remove_dir_all(&destination).unwrap();
+}
+
+#[tokio::test]
+async fn test_download_without_checksums() {
+    let server = MockServer::start();
+    let session = session(&server);
+    let destination = temp_folder("no-checksums");
+    let target = Target::Series(NO_CHECKSUMS.parse().unwrap());
+
+    let course = crawl(&server, &session, target, &selection(Quality::High, None))
+        .await
+        .unwrap();
+    assert_eq!(course.videos.len(), 2);
+    assert!(course.videos.iter().all(|video| video.checksum.is_none()));
+
+    let options = download_options(&destination, 1);
+
+    download::download_course(options, course.clone(), None, Arc::new(Silent), &session)
+        .await
+        .unwrap();
+
+    for video in &course.videos {
+        assert!(download::video_path(&destination, video).exists());
+    }
+
+    fs::remove_dir_all(&destination).unwrap();
+}
+
+#[tokio::test]
+async fn test_login() {
+    let server = MockServer::start();
+    let destination = temp_folder("login");
+    let target = Target::Series(MULTI.parse().unwrap());
+    server.require_login("alice", "secret");
+
+    let credentials = |password: &str| Credentials {
+        username: "alice".to_owned(),
+        password: password.to_owned(),
+    };
+
+    // A wrong password is rejected by redirecting back to the login page
+    let session = SessionBuilder::new(server.url.clone()).build().unwrap();
+    let err = session.login(&credentials("wrong")).await.unwrap_err();
+    assert!(matches!(err.downcast_ref(), Some(Error::LoginFailed(_))));
+    assert_eq!(error::exit_code(&err), error::EXIT_AUTH);
+    assert_eq!(server.logins(), 0);
+
+    let session = SessionBuilder::new(server.url.clone())
+        .credentials(credentials("secret"))
+        .build()
+        .unwrap();
+    session.login(&credentials("secret")).await.unwrap();
+    assert_eq!(server.logins(), 1);
+
+    let course = crawl(
+        &server,
+        &session,
+        target,
+        &selection(Quality::High, Some(vec![TrackType::Presenter])),
+    )
+    .await
+    .unwrap();
+    assert_eq!(course.videos.len(), 3);
+
+    // Once the session expires, the parallel downloads log in again (just once)
+    server.require_token("expired");
+    let options = download_options(&destination, 3);
+    download::download_course(options, course.clone(), None, Arc::new(Silent), &session)
+        .await
+        .unwrap();
+    assert_eq!(server.logins(), 2);
+
+    for video in &course.videos {
+        let name = video.url.rsplit('/').next().unwrap();
+        let path = download::video_path(&destination, video);
+        assert_eq!(fs::read(&path).unwrap(), common::media(name));
+    }
+
+    fs::remove_dir_all(&destination).unwrap();
+}